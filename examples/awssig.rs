@@ -1,15 +1,10 @@
-use std::ffi::{c_char, c_void};
 use std::ptr::addr_of;
 
 use http::HeaderMap;
-use ngx::core;
-use ngx::ffi::{
-    ngx_array_push, ngx_command_t, ngx_conf_t, ngx_http_core_module, ngx_http_handler_pt, ngx_http_module_t,
-    ngx_http_phases_NGX_HTTP_PRECONTENT_PHASE, ngx_int_t, ngx_module_t, ngx_str_t, ngx_uint_t, NGX_CONF_TAKE1,
-    NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MODULE, NGX_HTTP_SRV_CONF,
-};
+use ngx::core::{self, ConfError};
+use ngx::ffi::{ngx_conf_t, ngx_http_module_t, ngx_int_t, ngx_module_t, NGX_HTTP_MODULE};
 use ngx::http::*;
-use ngx::{http_request_handler, ngx_log_debug_http, ngx_null_command, ngx_string};
+use ngx::{http_commands, http_request_handler, ngx_log_debug_http};
 
 struct Module;
 
@@ -19,16 +14,11 @@ impl HTTPModule for Module {
     type LocConf = ModuleConfig;
 
     unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
-        let cmcf = ngx_http_conf_get_module_main_conf(cf, &*addr_of!(ngx_http_core_module));
-
-        let h = ngx_array_push(&mut (*cmcf).phases[ngx_http_phases_NGX_HTTP_PRECONTENT_PHASE as usize].handlers)
-            as *mut ngx_http_handler_pt;
-        if h.is_null() {
-            return core::Status::NGX_ERROR.into();
+        // set a PreContent phase handler
+        match register_phase_handler(&mut *cf, Phase::PreContent, awssigv4_header_handler) {
+            Ok(()) => core::Status::NGX_OK.into(),
+            Err(status) => status.into(),
         }
-        // set an phase handler
-        *h = Some(awssigv4_header_handler);
-        core::Status::NGX_OK.into()
     }
 }
 
@@ -37,53 +27,44 @@ struct ModuleConfig {
     enable: bool,
     access_key: String,
     secret_key: String,
-    s3_bucket: String,
+    s3_bucket: Option<ComplexValue>,
     s3_endpoint: String,
 }
 
-static mut NGX_HTTP_AWSSIGV4_COMMANDS: [ngx_command_t; 6] = [
-    ngx_command_t {
-        name: ngx_string!("awssigv4"),
-        type_: (NGX_HTTP_LOC_CONF | NGX_HTTP_SRV_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
-        set: Some(ngx_http_awssigv4_commands_set_enable),
-        conf: NGX_HTTP_LOC_CONF_OFFSET,
-        offset: 0,
-        post: std::ptr::null_mut(),
-    },
-    ngx_command_t {
-        name: ngx_string!("awssigv4_access_key"),
-        type_: (NGX_HTTP_LOC_CONF | NGX_HTTP_SRV_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
-        set: Some(ngx_http_awssigv4_commands_set_access_key),
-        conf: NGX_HTTP_LOC_CONF_OFFSET,
-        offset: 0,
-        post: std::ptr::null_mut(),
-    },
-    ngx_command_t {
-        name: ngx_string!("awssigv4_secret_key"),
-        type_: (NGX_HTTP_LOC_CONF | NGX_HTTP_SRV_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
-        set: Some(ngx_http_awssigv4_commands_set_secret_key),
-        conf: NGX_HTTP_LOC_CONF_OFFSET,
-        offset: 0,
-        post: std::ptr::null_mut(),
-    },
-    ngx_command_t {
-        name: ngx_string!("awssigv4_s3_bucket"),
-        type_: (NGX_HTTP_LOC_CONF | NGX_HTTP_SRV_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
-        set: Some(ngx_http_awssigv4_commands_set_s3_bucket),
-        conf: NGX_HTTP_LOC_CONF_OFFSET,
-        offset: 0,
-        post: std::ptr::null_mut(),
-    },
-    ngx_command_t {
-        name: ngx_string!("awssigv4_s3_endpoint"),
-        type_: (NGX_HTTP_LOC_CONF | NGX_HTTP_SRV_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
-        set: Some(ngx_http_awssigv4_commands_set_s3_endpoint),
-        conf: NGX_HTTP_LOC_CONF_OFFSET,
-        offset: 0,
-        post: std::ptr::null_mut(),
-    },
-    ngx_null_command!(),
-];
+http_commands! {
+    static mut NGX_HTTP_AWSSIGV4_COMMANDS = [
+        "awssigv4" => {
+            context: srv | loc,
+            args: 1,
+            conf: loc,
+            set: ngx_http_awssigv4_commands_set_enable,
+        },
+        "awssigv4_access_key" => {
+            context: srv | loc,
+            args: 1,
+            conf: loc,
+            set: ngx_http_awssigv4_commands_set_access_key,
+        },
+        "awssigv4_secret_key" => {
+            context: srv | loc,
+            args: 1,
+            conf: loc,
+            set: ngx_http_awssigv4_commands_set_secret_key,
+        },
+        "awssigv4_s3_bucket" => {
+            context: srv | loc,
+            args: 1,
+            conf: loc,
+            set: ngx_http_awssigv4_commands_set_s3_bucket,
+        },
+        "awssigv4_s3_endpoint" => {
+            context: srv | loc,
+            args: 1,
+            conf: loc,
+            set: ngx_http_awssigv4_commands_set_s3_endpoint,
+        },
+    ];
+}
 
 static NGX_HTTP_AWSSIGV4_MODULE_CTX: ngx_http_module_t = ngx_http_module_t {
     preconfiguration: Some(Module::preconfiguration),
@@ -125,7 +106,7 @@ impl Merge for ModuleConfig {
             });
         }
         if self.enable && self.access_key.is_empty() {
-            return Err(MergeConfigError::NoValue);
+            return Err(MergeConfigError::MissingField("awssigv4_access_key"));
         }
 
         if self.secret_key.is_empty() {
@@ -136,18 +117,14 @@ impl Merge for ModuleConfig {
             });
         }
         if self.enable && self.secret_key.is_empty() {
-            return Err(MergeConfigError::NoValue);
+            return Err(MergeConfigError::MissingField("awssigv4_secret_key"));
         }
 
-        if self.s3_bucket.is_empty() {
-            self.s3_bucket = String::from(if !prev.s3_bucket.is_empty() {
-                &prev.s3_bucket
-            } else {
-                ""
-            });
+        if self.s3_bucket.is_none() {
+            self.s3_bucket = prev.s3_bucket.clone();
         }
-        if self.enable && self.s3_bucket.is_empty() {
-            return Err(MergeConfigError::NoValue);
+        if self.enable && self.s3_bucket.is_none() {
+            return Err(MergeConfigError::MissingField("awssigv4_s3_bucket"));
         }
 
         if self.s3_endpoint.is_empty() {
@@ -161,86 +138,34 @@ impl Merge for ModuleConfig {
     }
 }
 
-extern "C" fn ngx_http_awssigv4_commands_set_enable(
-    cf: *mut ngx_conf_t,
-    _cmd: *mut ngx_command_t,
-    conf: *mut c_void,
-) -> *mut c_char {
-    unsafe {
-        let conf = &mut *(conf as *mut ModuleConfig);
-        let args = (*(*cf).args).elts as *mut ngx_str_t;
-        let val = (*args.add(1)).to_str();
-
-        // set default value optionally
-        conf.enable = false;
-
-        if val.len() == 2 && val.eq_ignore_ascii_case("on") {
-            conf.enable = true;
-        } else if val.len() == 3 && val.eq_ignore_ascii_case("off") {
-            conf.enable = false;
-        }
-    };
-
-    std::ptr::null_mut()
+fn ngx_http_awssigv4_commands_set_enable(cf: &mut ngx_conf_t, conf: &mut ModuleConfig) -> Result<(), ConfError> {
+    conf.enable = core::parse_flag(cf, 1)?;
+    Ok(())
 }
 
-extern "C" fn ngx_http_awssigv4_commands_set_access_key(
-    cf: *mut ngx_conf_t,
-    _cmd: *mut ngx_command_t,
-    conf: *mut c_void,
-) -> *mut c_char {
-    unsafe {
-        let conf = &mut *(conf as *mut ModuleConfig);
-        let args = (*(*cf).args).elts as *mut ngx_str_t;
-        conf.access_key = (*args.add(1)).to_string();
-    };
-
-    std::ptr::null_mut()
+fn ngx_http_awssigv4_commands_set_access_key(cf: &mut ngx_conf_t, conf: &mut ModuleConfig) -> Result<(), ConfError> {
+    conf.access_key = cf.args()[1].to_string();
+    Ok(())
 }
 
-extern "C" fn ngx_http_awssigv4_commands_set_secret_key(
-    cf: *mut ngx_conf_t,
-    _cmd: *mut ngx_command_t,
-    conf: *mut c_void,
-) -> *mut c_char {
-    unsafe {
-        let conf = &mut *(conf as *mut ModuleConfig);
-        let args = (*(*cf).args).elts as *mut ngx_str_t;
-        conf.secret_key = (*args.add(1)).to_string();
-    };
-
-    std::ptr::null_mut()
+fn ngx_http_awssigv4_commands_set_secret_key(cf: &mut ngx_conf_t, conf: &mut ModuleConfig) -> Result<(), ConfError> {
+    conf.secret_key = cf.args()[1].to_string();
+    Ok(())
 }
 
-extern "C" fn ngx_http_awssigv4_commands_set_s3_bucket(
-    cf: *mut ngx_conf_t,
-    _cmd: *mut ngx_command_t,
-    conf: *mut c_void,
-) -> *mut c_char {
-    unsafe {
-        let conf = &mut *(conf as *mut ModuleConfig);
-        let args = (*(*cf).args).elts as *mut ngx_str_t;
-        conf.s3_bucket = (*args.add(1)).to_string();
-        if conf.s3_bucket.len() == 1 {
-            println!("Validation failed");
-            return ngx::core::NGX_CONF_ERROR as _;
-        }
-    };
-    std::ptr::null_mut()
+fn ngx_http_awssigv4_commands_set_s3_bucket(cf: &mut ngx_conf_t, conf: &mut ModuleConfig) -> Result<(), ConfError> {
+    let s3_bucket = ComplexValue::parse(cf, 1)?;
+    if s3_bucket.static_value().is_some_and(|v| v.as_bytes().len() == 1) {
+        println!("Validation failed");
+        return Err(ConfError::Reported);
+    }
+    conf.s3_bucket = Some(s3_bucket);
+    Ok(())
 }
 
-extern "C" fn ngx_http_awssigv4_commands_set_s3_endpoint(
-    cf: *mut ngx_conf_t,
-    _cmd: *mut ngx_command_t,
-    conf: *mut c_void,
-) -> *mut c_char {
-    unsafe {
-        let conf = &mut *(conf as *mut ModuleConfig);
-        let args = (*(*cf).args).elts as *mut ngx_str_t;
-        conf.s3_endpoint = (*args.add(1)).to_string();
-    };
-
-    std::ptr::null_mut()
+fn ngx_http_awssigv4_commands_set_s3_endpoint(cf: &mut ngx_conf_t, conf: &mut ModuleConfig) -> Result<(), ConfError> {
+    conf.s3_endpoint = cf.args()[1].to_string();
+    Ok(())
 }
 
 http_request_handler!(awssigv4_header_handler, |request: &mut Request| {
@@ -264,9 +189,15 @@ http_request_handler!(awssigv4_header_handler, |request: &mut Request| {
         return HTTPStatus::FORBIDDEN.into();
     }
 
+    let s3_bucket = match conf.s3_bucket.as_ref().map(|cv| cv.evaluate(request)) {
+        Some(Ok(v)) => v.to_string_lossy(),
+        Some(Err(status)) => return status,
+        None => return core::Status::NGX_DECLINED,
+    };
+
     let datetime = chrono::Utc::now();
     let uri = match request.unparsed_uri().to_str() {
-        Ok(v) => format!("https://{}.{}{}", conf.s3_bucket, conf.s3_endpoint, v),
+        Ok(v) => format!("https://{}.{}{}", s3_bucket, conf.s3_endpoint, v),
         Err(_) => return core::Status::NGX_DECLINED,
     };
 
@@ -1,14 +1,9 @@
-use std::ffi::{c_char, c_void};
 use std::ptr::addr_of;
 
-use ngx::core;
-use ngx::ffi::{
-    ngx_array_push, ngx_command_t, ngx_conf_t, ngx_http_core_module, ngx_http_handler_pt, ngx_http_module_t,
-    ngx_http_phases_NGX_HTTP_ACCESS_PHASE, ngx_int_t, ngx_module_t, ngx_str_t, ngx_uint_t, NGX_CONF_TAKE1,
-    NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MODULE,
-};
+use ngx::core::{self, ConfError};
+use ngx::ffi::{ngx_conf_t, ngx_http_module_t, ngx_int_t, ngx_module_t, NGX_HTTP_MODULE};
 use ngx::http::{self, HTTPModule, MergeConfigError};
-use ngx::{http_request_handler, ngx_log_debug_http, ngx_null_command, ngx_string};
+use ngx::{http_commands, http_request_handler, ngx_log_debug_http};
 
 struct Module;
 
@@ -18,16 +13,11 @@ impl http::HTTPModule for Module {
     type LocConf = ModuleConfig;
 
     unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
-        let cmcf = http::ngx_http_conf_get_module_main_conf(cf, &*addr_of!(ngx_http_core_module));
-
-        let h = ngx_array_push(&mut (*cmcf).phases[ngx_http_phases_NGX_HTTP_ACCESS_PHASE as usize].handlers)
-            as *mut ngx_http_handler_pt;
-        if h.is_null() {
-            return core::Status::NGX_ERROR.into();
-        }
         // set an Access phase handler
-        *h = Some(curl_access_handler);
-        core::Status::NGX_OK.into()
+        match http::register_phase_handler(&mut *cf, http::Phase::Access, curl_access_handler) {
+            Ok(()) => core::Status::NGX_OK.into(),
+            Err(status) => status.into(),
+        }
     }
 }
 
@@ -36,17 +26,16 @@ struct ModuleConfig {
     enable: bool,
 }
 
-static mut NGX_HTTP_CURL_COMMANDS: [ngx_command_t; 2] = [
-    ngx_command_t {
-        name: ngx_string!("curl"),
-        type_: (NGX_HTTP_LOC_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
-        set: Some(ngx_http_curl_commands_set_enable),
-        conf: NGX_HTTP_LOC_CONF_OFFSET,
-        offset: 0,
-        post: std::ptr::null_mut(),
-    },
-    ngx_null_command!(),
-];
+http_commands! {
+    static mut NGX_HTTP_CURL_COMMANDS = [
+        "curl" => {
+            context: loc,
+            args: 1,
+            conf: loc,
+            set: ngx_http_curl_commands_set_enable,
+        },
+    ];
+}
 
 static NGX_HTTP_CURL_MODULE_CTX: ngx_http_module_t = ngx_http_module_t {
     preconfiguration: Some(Module::preconfiguration),
@@ -104,26 +93,7 @@ http_request_handler!(curl_access_handler, |request: &mut http::Request| {
     }
 });
 
-extern "C" fn ngx_http_curl_commands_set_enable(
-    cf: *mut ngx_conf_t,
-    _cmd: *mut ngx_command_t,
-    conf: *mut c_void,
-) -> *mut c_char {
-    unsafe {
-        let conf = &mut *(conf as *mut ModuleConfig);
-        let args = (*(*cf).args).elts as *mut ngx_str_t;
-
-        let val = (*args.add(1)).to_str();
-
-        // set default value optionally
-        conf.enable = false;
-
-        if val.len() == 2 && val.eq_ignore_ascii_case("on") {
-            conf.enable = true;
-        } else if val.len() == 3 && val.eq_ignore_ascii_case("off") {
-            conf.enable = false;
-        }
-    };
-
-    std::ptr::null_mut()
+fn ngx_http_curl_commands_set_enable(cf: &mut ngx_conf_t, conf: &mut ModuleConfig) -> Result<(), ConfError> {
+    conf.enable = core::parse_flag(cf, 1)?;
+    Ok(())
 }
@@ -3,12 +3,11 @@ use std::ptr::addr_of;
 
 use ngx::core;
 use ngx::ffi::{
-    in_port_t, ngx_conf_t, ngx_connection_local_sockaddr, ngx_http_add_variable, ngx_http_module_t,
-    ngx_http_variable_t, ngx_inet_get_port, ngx_int_t, ngx_module_t, ngx_sock_ntop, ngx_str_t, ngx_variable_value_t,
-    sockaddr, sockaddr_storage, INET_ADDRSTRLEN, NGX_HTTP_MODULE,
+    in_port_t, ngx_connection_local_sockaddr, ngx_http_module_t, ngx_inet_get_port, ngx_module_t, ngx_sock_ntop,
+    ngx_str_t, sockaddr, sockaddr_storage, INET_ADDRSTRLEN, NGX_HTTP_MODULE,
 };
-use ngx::http::{self, HTTPModule};
-use ngx::{http_variable_get, ngx_http_null_variable, ngx_log_debug_http, ngx_null_string, ngx_string};
+use ngx::http::{self, HTTPModule, Variable};
+use ngx::{ngx_log_debug_http, ngx_null_string};
 
 const IPV4_STRLEN: usize = INET_ADDRSTRLEN as usize;
 
@@ -49,30 +48,18 @@ impl NgxHttpOrigDstCtx {
         core::Status::NGX_OK
     }
 
-    pub unsafe fn bind_addr(&self, v: *mut ngx_variable_value_t) {
+    pub fn addr(&self) -> Option<&[u8]> {
         if self.orig_dst_addr.len == 0 {
-            (*v).set_not_found(1);
-            return;
+            return None;
         }
-
-        (*v).set_valid(1);
-        (*v).set_no_cacheable(0);
-        (*v).set_not_found(0);
-        (*v).set_len(self.orig_dst_addr.len as u32);
-        (*v).data = self.orig_dst_addr.data;
+        Some(self.orig_dst_addr.as_bytes())
     }
 
-    pub unsafe fn bind_port(&self, v: *mut ngx_variable_value_t) {
+    pub fn port(&self) -> Option<&[u8]> {
         if self.orig_dst_port.len == 0 {
-            (*v).set_not_found(1);
-            return;
+            return None;
         }
-
-        (*v).set_valid(1);
-        (*v).set_no_cacheable(0);
-        (*v).set_not_found(0);
-        (*v).set_len(self.orig_dst_port.len as u32);
-        (*v).data = self.orig_dst_port.data;
+        Some(self.orig_dst_port.as_bytes())
     }
 }
 
@@ -102,32 +89,6 @@ pub static mut ngx_http_orig_dst_module: ngx_module_t = ngx_module_t {
     ..ngx_module_t::default()
 };
 
-static mut NGX_HTTP_ORIG_DST_VARS: [ngx_http_variable_t; 3] = [
-    // ngx_str_t name
-    // ngx_http_set_variable_pt set_handler
-    // ngx_http_get_variable_pt get_handler
-    // uintptr_t data
-    // ngx_uint_t flags
-    // ngx_uint_t index
-    ngx_http_variable_t {
-        name: ngx_string!("server_orig_addr"),
-        set_handler: None,
-        get_handler: Some(ngx_http_orig_dst_addr_variable),
-        data: 0,
-        flags: 0,
-        index: 0,
-    },
-    ngx_http_variable_t {
-        name: ngx_string!("server_orig_port"),
-        set_handler: None,
-        get_handler: Some(ngx_http_orig_dst_port_variable),
-        data: 0,
-        flags: 0,
-        index: 0,
-    },
-    ngx_http_null_variable!(),
-];
-
 unsafe fn ngx_get_origdst(request: &mut http::Request) -> Result<(String, in_port_t), core::Status> {
     let c = request.connection();
 
@@ -188,83 +149,39 @@ unsafe fn ngx_get_origdst(request: &mut http::Request) -> Result<(String, in_por
     Ok((String::from_utf8(ip).unwrap(), port))
 }
 
-http_variable_get!(
-    ngx_http_orig_dst_addr_variable,
-    |request: &mut http::Request, v: *mut ngx_variable_value_t, _: usize| {
-        let ctx = request.get_module_ctx::<NgxHttpOrigDstCtx>(&*addr_of!(ngx_http_orig_dst_module));
-        if let Some(obj) = ctx {
-            ngx_log_debug_http!(request, "httporigdst: found context and binding variable",);
-            obj.bind_addr(v);
-            return core::Status::NGX_OK;
-        }
-        // lazy initialization:
-        //   get original dest information
-        //   create context
-        //   set context
-        // bind address
-        ngx_log_debug_http!(request, "httporigdst: context not found, getting address");
-        let r = ngx_get_origdst(request);
-        match r {
-            Err(e) => {
-                return e;
-            }
-            Ok((ip, port)) => {
-                // create context,
-                // set context
-                let new_ctx = request.pool().allocate::<NgxHttpOrigDstCtx>(Default::default());
-
-                if new_ctx.is_null() {
-                    return core::Status::NGX_ERROR;
-                }
-
-                ngx_log_debug_http!(request, "httporigdst: saving ip - {:?}, port - {}", ip, port,);
-                (*new_ctx).save(&ip, port, &mut request.pool());
-                (*new_ctx).bind_addr(v);
-                request.set_module_ctx(new_ctx as *mut c_void, &*addr_of!(ngx_http_orig_dst_module));
-            }
-        }
-        core::Status::NGX_OK
+/// Returns the context with the original destination, `None` if it's not available for the
+/// connection, or an error.
+fn orig_dst_ctx(request: &mut http::Request) -> Result<Option<&NgxHttpOrigDstCtx>, core::Status> {
+    let module = unsafe { &*addr_of!(ngx_http_orig_dst_module) };
+    if let Some(obj) = request.get_module_ctx::<NgxHttpOrigDstCtx>(module) {
+        ngx_log_debug_http!(request, "httporigdst: found context and binding variable",);
+        // SAFETY: the context is allocated from the request pool and outlives the request reference.
+        return Ok(Some(unsafe { &*(obj as *const NgxHttpOrigDstCtx) }));
     }
-);
-
-http_variable_get!(
-    ngx_http_orig_dst_port_variable,
-    |request: &mut http::Request, v: *mut ngx_variable_value_t, _: usize| {
-        let ctx = request.get_module_ctx::<NgxHttpOrigDstCtx>(&*addr_of!(ngx_http_orig_dst_module));
-        if let Some(obj) = ctx {
-            ngx_log_debug_http!(request, "httporigdst: found context and binding variable",);
-            obj.bind_port(v);
-            return core::Status::NGX_OK;
-        }
-        // lazy initialization:
-        //   get original dest information
-        //   create context
-        //   set context
-        // bind port
-        ngx_log_debug_http!(request, "httporigdst: context not found, getting address");
-        let r = ngx_get_origdst(request);
-        match r {
-            Err(e) => {
-                return e;
-            }
-            Ok((ip, port)) => {
-                // create context,
-                // set context
-                let new_ctx = request.pool().allocate::<NgxHttpOrigDstCtx>(Default::default());
+    // lazy initialization:
+    //   get original dest information
+    //   create context
+    //   set context
+    ngx_log_debug_http!(request, "httporigdst: context not found, getting address");
+    let (ip, port) = match unsafe { ngx_get_origdst(request) } {
+        Ok(dst) => dst,
+        Err(core::Status::NGX_DECLINED) => return Ok(None),
+        Err(status) => return Err(status),
+    };
 
-                if new_ctx.is_null() {
-                    return core::Status::NGX_ERROR;
-                }
+    let new_ctx = request.pool().allocate::<NgxHttpOrigDstCtx>(Default::default());
+    if new_ctx.is_null() {
+        return Err(core::Status::NGX_ERROR);
+    }
 
-                ngx_log_debug_http!(request, "httporigdst: saving ip - {:?}, port - {}", ip, port,);
-                (*new_ctx).save(&ip, port, &mut request.pool());
-                (*new_ctx).bind_port(v);
-                request.set_module_ctx(new_ctx as *mut c_void, &*addr_of!(ngx_http_orig_dst_module));
-            }
-        }
-        core::Status::NGX_OK
+    ngx_log_debug_http!(request, "httporigdst: saving ip - {:?}, port - {}", ip, port,);
+    let new_ctx = unsafe { &mut *new_ctx };
+    if new_ctx.save(&ip, port, &mut request.pool()) != core::Status::NGX_OK {
+        return Err(core::Status::NGX_ERROR);
     }
-);
+    request.set_module_ctx(new_ctx as *mut _ as *mut c_void, module);
+    Ok(Some(new_ctx))
+}
 
 struct Module;
 
@@ -273,19 +190,8 @@ impl HTTPModule for Module {
     type SrvConf = ();
     type LocConf = ();
 
-    // static ngx_int_t ngx_http_orig_dst_add_variables(ngx_conf_t *cf)
-    unsafe extern "C" fn preconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
-        for mut v in NGX_HTTP_ORIG_DST_VARS {
-            if v.name.len == 0 {
-                break;
-            }
-            let var = ngx_http_add_variable(cf, &mut v.name, v.flags);
-            if var.is_null() {
-                return core::Status::NGX_ERROR.into();
-            }
-            (*var).get_handler = v.get_handler;
-            (*var).data = v.data;
-        }
-        core::Status::NGX_OK.into()
-    }
+    const VARIABLES: &'static [Variable] = &[
+        Variable::new("server_orig_addr").getter(|request| Ok(orig_dst_ctx(request)?.and_then(|ctx| ctx.addr()))),
+        Variable::new("server_orig_port").getter(|request| Ok(orig_dst_ctx(request)?.and_then(|ctx| ctx.port()))),
+    ];
 }
@@ -36,15 +36,23 @@ const NGX_CONF_FEATURES: &[&str] = &[
     "http_v2",
     "http_v3",
     "http_x_forwarded_for",
+    "mail",
     "pcre",
     "pcre2",
     "quic",
     "ssl",
+    "stream",
     "stream_ssl",
     "stream_upstream_zone",
     "threads",
 ];
 
+/// The optional nginx modules detected by the presence of the headers in the include paths
+///
+/// These are not reported via `ngx_auto_config.h`, but are otherwise exposed the same way as the
+/// features from [`NGX_CONF_FEATURES`].
+const NGX_CONF_MODULES: &[(&str, &str)] = &[("mail", "ngx_mail.h"), ("stream", "ngx_stream.h")];
+
 /// The operating systems supported by the nginx configuration script
 ///
 /// The detected value will be exposed to the buildsrcipts of _direct_ dependents of this crate as
@@ -323,7 +331,21 @@ RUST_CONF_NGINX_VERSION_NUMBER=nginx_version
 "
     )?;
 
+    for (module, header) in NGX_CONF_MODULES {
+        let module = module.to_ascii_uppercase();
+        write!(
+            writer,
+            "
+#if __has_include(<{header}>)
+RUST_CONF_{module}=1
+#endif"
+        )?;
+    }
+
     for flag in NGX_CONF_FEATURES.iter().chain(NGX_CONF_OS.iter()) {
+        if NGX_CONF_MODULES.iter().any(|(module, _)| module == flag) {
+            continue;
+        }
         let flag = flag.to_ascii_uppercase();
         write!(
             writer,
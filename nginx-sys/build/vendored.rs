@@ -72,7 +72,7 @@ const ALL_SERVERS_AND_PUBLIC_KEY_IDS: [(&str, &str); 4] = [
 ];
 
 /// List of configure switches specifying the modules to build nginx with
-const NGX_BASE_MODULES: [&str; 20] = [
+const NGX_BASE_MODULES: [&str; 22] = [
     "--with-compat",
     "--with-http_addition_module",
     "--with-http_auth_request_module",
@@ -88,6 +88,8 @@ const NGX_BASE_MODULES: [&str; 20] = [
     "--with-http_stub_status_module",
     "--with-http_sub_module",
     "--with-http_v2_module",
+    "--with-mail_ssl_module",
+    "--with-mail",
     "--with-stream_realip_module",
     "--with-stream_ssl_module",
     "--with-stream_ssl_preread_module",
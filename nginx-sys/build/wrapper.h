@@ -3,6 +3,16 @@
 #include <ngx_config.h>
 #include <ngx_core.h>
 
+// The mail and stream module headers are only available if nginx was configured `--with-mail` or
+// `--with-stream` respectively.
+#if __has_include(<ngx_mail.h>)
+#include <ngx_mail.h>
+#endif
+
+#if __has_include(<ngx_stream.h>)
+#include <ngx_stream.h>
+#endif
+
 const char *NGX_RS_MODULE_SIGNATURE = NGX_MODULE_SIGNATURE;
 
 // `--prefix=` results in not emitting the declaration
@@ -0,0 +1,94 @@
+use core::ptr;
+
+use crate::bindings::*;
+
+/// Adds the event to the queue of the posted events, unless it is already posted.
+///
+/// The posted events are processed by the worker process after the pending I/O events.
+/// This is the Rust counterpart of the `ngx_post_event` macro.
+///
+/// # Safety
+///
+/// The `ev` must be a valid event that remains valid until it is processed or removed with
+/// [`ngx_delete_posted_event`], and the `q` must be a valid queue, e.g. `ngx_posted_events`.
+/// Must be called from the thread running the event loop.
+pub unsafe fn ngx_post_event(ev: *mut ngx_event_t, q: *mut ngx_queue_t) {
+    if (*ev).posted() != 0 {
+        return;
+    }
+
+    (*ev).set_posted(1);
+
+    // ngx_queue_insert_tail
+    let queue = ptr::addr_of_mut!((*ev).queue);
+    (*queue).prev = (*q).prev;
+    (*(*queue).prev).next = queue;
+    (*queue).next = q;
+    (*q).prev = queue;
+}
+
+/// Removes the event from the queue of the posted events.
+///
+/// This is the Rust counterpart of the `ngx_delete_posted_event` macro.
+///
+/// # Safety
+///
+/// The `ev` must be a valid posted event. Must be called from the thread running the event loop.
+pub unsafe fn ngx_delete_posted_event(ev: *mut ngx_event_t) {
+    (*ev).set_posted(0);
+
+    // ngx_queue_remove
+    let queue = ptr::addr_of_mut!((*ev).queue);
+    (*(*queue).next).prev = (*queue).prev;
+    (*(*queue).prev).next = (*queue).next;
+    (*queue).prev = ptr::null_mut();
+    (*queue).next = ptr::null_mut();
+}
+
+/// Sets the event timer to expire in `timer` milliseconds.
+///
+/// An already set timer is only updated if the new expiration time differs by at least
+/// `NGX_TIMER_LAZY_DELAY` milliseconds. This is the Rust counterpart of the inline
+/// `ngx_event_add_timer` function.
+///
+/// # Safety
+///
+/// The `ev` must be a valid event that remains valid until the timer expires or is removed with
+/// [`ngx_event_del_timer`]. Must be called from the thread running the event loop.
+pub unsafe fn ngx_event_add_timer(ev: *mut ngx_event_t, timer: ngx_msec_t) {
+    let key = ngx_current_msec.wrapping_add(timer);
+
+    if (*ev).timer_set() != 0 {
+        // Do not update the timer if the difference is insignificant, to reduce the rbtree
+        // operations for the fast connections.
+        let diff = key.wrapping_sub((*ev).timer.key) as ngx_msec_int_t;
+        if diff.unsigned_abs() < NGX_TIMER_LAZY_DELAY as _ {
+            return;
+        }
+
+        ngx_event_del_timer(ev);
+    }
+
+    (*ev).timer.key = key;
+    ngx_rbtree_insert(
+        ptr::addr_of_mut!(ngx_event_timer_rbtree),
+        ptr::addr_of_mut!((*ev).timer),
+    );
+    (*ev).set_timer_set(1);
+}
+
+/// Removes the event timer.
+///
+/// This is the Rust counterpart of the inline `ngx_event_del_timer` function.
+///
+/// # Safety
+///
+/// The `ev` must be a valid event with the timer set. Must be called from the thread running the
+/// event loop.
+pub unsafe fn ngx_event_del_timer(ev: *mut ngx_event_t) {
+    ngx_rbtree_delete(
+        ptr::addr_of_mut!(ngx_event_timer_rbtree),
+        ptr::addr_of_mut!((*ev).timer),
+    );
+    (*ev).set_timer_set(0);
+}
@@ -7,6 +7,8 @@ use core::mem::offset_of;
 use core::ptr::copy_nonoverlapping;
 use core::slice;
 
+mod event;
+
 #[doc(hidden)]
 mod bindings {
     #![allow(missing_docs)]
@@ -21,6 +23,7 @@ mod bindings {
 }
 #[doc(no_inline)]
 pub use bindings::*;
+pub use event::*;
 
 /// The offset of the `main_conf` field in the `ngx_http_conf_ctx_t` struct.
 ///
@@ -190,6 +193,21 @@ impl TryFrom<ngx_str_t> for &str {
     }
 }
 
+impl ngx_conf_t {
+    /// Returns the arguments of the directive currently being processed.
+    ///
+    /// The first element is the directive name itself, followed by the directive arguments.
+    #[inline]
+    pub fn args(&self) -> &[ngx_str_t] {
+        // SAFETY: `args` is either NULL or a valid array of `ngx_str_t` allocated in the
+        // configuration pool.
+        match unsafe { self.args.as_ref() } {
+            Some(args) if args.nelts != 0 => unsafe { slice::from_raw_parts(args.elts.cast(), args.nelts) },
+            _ => &[],
+        }
+    }
+}
+
 impl ngx_module_t {
     /// Create a new `ngx_module_t` instance with default values.
     pub const fn default() -> Self {
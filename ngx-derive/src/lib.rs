@@ -0,0 +1,155 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Expr, Field, Fields, Index, Member};
+
+/// Derive macro for the `ngx::http::Merge` trait.
+///
+/// Every field is merged with the nginx `ngx_conf_merge_*_value` semantics: if the field is unset
+/// in the current configuration, the value from the enclosing configuration block is used. A field
+/// is considered unset according to the `ngx::http::Unset` trait, e.g. `None` for `Option<T>` or
+/// `NGX_CONF_UNSET` for integers, so the `Default` implementation of the structure is expected to
+/// return unset values.
+///
+/// The field values are copied with `Clone`.
+///
+/// # Field attributes
+///
+/// * `#[merge(default = <expr>)]` - value to use if the field is unset at both levels. The
+///   expression is converted with `Unset::from_value`, i.e. `Option<T>` fields accept `T`.
+/// * `#[merge(required)]` - fail with `MergeConfigError::MissingField` if the field is unset at both
+///   levels.
+/// * `#[merge(nested)]` - merge the field with its own `Merge` implementation.
+/// * `#[merge(skip)]` - leave the field as is.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use ngx::http::Merge;
+///
+/// #[derive(Default, Merge)]
+/// struct LocConf {
+///     #[merge(default = false)]
+///     enable: Option<bool>,
+///     #[merge(required)]
+///     upstream: Option<String>,
+///     #[merge(default = 60000)]
+///     timeout: ngx_msec_t,
+/// }
+/// ```
+#[proc_macro_derive(Merge, attributes(merge))]
+pub fn derive_merge(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_merge(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    default: Option<Expr>,
+    required: bool,
+    nested: bool,
+    skip: bool,
+}
+
+impl FieldOptions {
+    fn from_field(field: &Field) -> syn::Result<Self> {
+        let mut options = FieldOptions::default();
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("merge")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    options.default = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("required") {
+                    options.required = true;
+                } else if meta.path.is_ident("nested") {
+                    options.nested = true;
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else {
+                    return Err(meta.error("unsupported merge attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        if (options.nested || options.skip) && (options.default.is_some() || options.required) {
+            return Err(Error::new_spanned(
+                field,
+                "`nested` and `skip` fields cannot have `default` or `required` attributes",
+            ));
+        }
+
+        if options.nested && options.skip {
+            return Err(Error::new_spanned(field, "`nested` and `skip` are mutually exclusive"));
+        }
+
+        Ok(options)
+    }
+}
+
+fn expand_merge(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(Error::new_spanned(&input, "Merge can only be derived for structs")),
+    };
+
+    let mut stmts = Vec::new();
+
+    for (index, field) in fields.iter().enumerate() {
+        let options = FieldOptions::from_field(field)?;
+        let (member, name) = match (&field.ident, fields) {
+            (Some(ident), Fields::Named(_)) => (Member::Named(ident.clone()), ident.to_string()),
+            _ => (Member::Unnamed(Index::from(index)), index.to_string()),
+        };
+
+        if options.skip {
+            continue;
+        }
+
+        if options.nested {
+            stmts.push(quote! {
+                ::ngx::http::Merge::merge(&mut self.#member, &prev.#member)?;
+            });
+            continue;
+        }
+
+        stmts.push(quote! {
+            if ::ngx::http::Unset::is_unset(&self.#member) {
+                self.#member = ::core::clone::Clone::clone(&prev.#member);
+            }
+        });
+
+        if let Some(default) = options.default {
+            let ty = &field.ty;
+            stmts.push(quote! {
+                if ::ngx::http::Unset::is_unset(&self.#member) {
+                    self.#member = <#ty as ::ngx::http::Unset>::from_value(#default);
+                }
+            });
+        }
+
+        if options.required {
+            stmts.push(quote! {
+                if ::ngx::http::Unset::is_unset(&self.#member) {
+                    return ::core::result::Result::Err(::ngx::http::MergeConfigError::MissingField(#name));
+                }
+            });
+        }
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::ngx::http::Merge for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn merge(&mut self, prev: &Self) -> ::core::result::Result<(), ::ngx::http::MergeConfigError> {
+                #( #stmts )*
+                ::core::result::Result::Ok(())
+            }
+        }
+    })
+}
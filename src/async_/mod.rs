@@ -0,0 +1,11 @@
+mod notify;
+mod sleep;
+mod spawn;
+#[cfg(ngx_feature = "threads")]
+mod thread_pool;
+
+pub use notify::*;
+pub use sleep::*;
+pub use spawn::*;
+#[cfg(ngx_feature = "threads")]
+pub use thread_pool::*;
@@ -0,0 +1,137 @@
+use core::ptr::{self, addr_of};
+use core::sync::atomic::{AtomicPtr, Ordering};
+use std::boxed::Box;
+
+use crate::core::Status;
+use crate::ffi::*;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A handle to schedule closures on the worker thread from other threads.
+///
+/// The closures are queued in a lock-free MPSC queue, and the event loop of the worker process is
+/// woken with `ngx_notify`, i.e. with an eventfd on Linux or `EVFILT_USER` with kqueue. The queued
+/// closures are invoked by the worker thread in the order of submission.
+///
+/// `ngx_notify` keeps a single handler for the worker process, shared with the NGINX thread
+/// pools. If a thread pool completion is signalled before the event loop handles the
+/// notification, the queued closures are invoked with the next notification instead.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let notifier = Notifier::new()?;
+///
+/// std::thread::spawn(move || {
+///     let result = compute();
+///     let _ = notifier.notify(move || {
+///         // Runs on the worker thread.
+///         store(result);
+///     });
+/// });
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Notifier {
+    pid: ngx_pid_t,
+}
+
+impl Notifier {
+    /// Creates a notifier for the current worker process.
+    ///
+    /// Must be called from the worker thread. Returns `Err(Status::NGX_ERROR)` outside of a worker
+    /// process, e.g. from the configuration handlers in the master process, or if the event method
+    /// does not support `ngx_notify`.
+    pub fn new() -> Result<Self, Status> {
+        // SAFETY: the globals are only modified on the process start.
+        unsafe {
+            let process = ngx_process as u32;
+            if process != NGX_PROCESS_WORKER && process != NGX_PROCESS_SINGLE {
+                return Err(Status::NGX_ERROR);
+            }
+
+            if (*addr_of!(ngx_event_actions)).notify.is_none() {
+                return Err(Status::NGX_ERROR);
+            }
+
+            Ok(Notifier { pid: ngx_pid })
+        }
+    }
+
+    /// Schedules the closure to run on the worker thread.
+    ///
+    /// Can be called from any thread of the worker process that created the notifier. Returns
+    /// `Err(Status::NGX_ERROR)` if the event loop cannot be notified. The closure remains queued
+    /// in this case and is invoked with the next successful notification.
+    pub fn notify<F>(&self, f: F) -> Result<(), Status>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // SAFETY: `ngx_pid` is only modified on the process start. The notifier copied to a forked
+        // process must not wake the event loop of the new process, which has its own queue.
+        if unsafe { ngx_pid } != self.pid {
+            return Err(Status::NGX_ERROR);
+        }
+
+        let node = Box::into_raw(Box::new(Node {
+            job: Box::new(f),
+            next: ptr::null_mut(),
+        }));
+
+        push(node);
+
+        // SAFETY: `ngx_notify` can be called from any thread, and the availability is checked in
+        // `Notifier::new`.
+        let rc = match unsafe { (*addr_of!(ngx_event_actions)).notify } {
+            Some(notify) => unsafe { notify(Some(notify_handler)) },
+            None => Status::NGX_ERROR.into(),
+        };
+
+        if rc != Status::NGX_OK.into() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        Ok(())
+    }
+}
+
+struct Node {
+    job: Job,
+    next: *mut Node,
+}
+
+/// The lock-free stack of the queued closures, in the reverse order of submission.
+static QUEUE: AtomicPtr<Node> = AtomicPtr::new(ptr::null_mut());
+
+fn push(node: *mut Node) {
+    let mut head = QUEUE.load(Ordering::Relaxed);
+    loop {
+        // SAFETY: the node is not shared until it is pushed.
+        unsafe { (*node).next = head };
+
+        match QUEUE.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed) {
+            Ok(_) => return,
+            Err(current) => head = current,
+        }
+    }
+}
+
+/// The `ngx_notify` handler, invoked by the event loop of the worker process.
+unsafe extern "C" fn notify_handler(_ev: *mut ngx_event_t) {
+    // The closures queued after this point send another notification.
+    let mut head = QUEUE.swap(ptr::null_mut(), Ordering::Acquire);
+
+    // Restore the order of submission.
+    let mut jobs = ptr::null_mut::<Node>();
+    while !head.is_null() {
+        let next = (*head).next;
+        (*head).next = jobs;
+        jobs = head;
+        head = next;
+    }
+
+    while !jobs.is_null() {
+        let node = Box::from_raw(jobs);
+        jobs = node.next;
+        (node.job)();
+    }
+}
@@ -0,0 +1,238 @@
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use std::boxed::Box;
+
+use crate::ffi::*;
+
+/// A callback timer, based on the NGINX event timers.
+///
+/// The callback is invoked by the worker thread once the timer expires. The timer is cancelled
+/// when dropped. To bind the timer to a request or another object with a pool, move it to the
+/// pool with [`Pool::allocate`](crate::core::Pool::allocate).
+///
+/// The timers do not delay the graceful shutdown of the worker process, i.e. the pending timers
+/// are dropped without invoking the callbacks.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let timer = Timer::after(Duration::from_secs(5), move || {
+///     ngx_log_debug!(log, "deadline expired");
+/// });
+/// request.pool().allocate(timer);
+/// ```
+pub struct Timer(Box<TimerEvent<Callback>>);
+
+type Callback = Option<Box<dyn FnOnce()>>;
+
+impl Timer {
+    /// Creates a timer invoking the callback after the `delay`.
+    ///
+    /// Must be called from the worker thread.
+    pub fn after<F>(delay: Duration, callback: F) -> Timer
+    where
+        F: FnOnce() + 'static,
+    {
+        let mut timer = Timer(TimerEvent::new(Some(Box::new(callback)), timer_handler));
+        timer.0.add(duration_to_msec(delay));
+        timer
+    }
+
+    /// Returns `true` if the timer has not expired yet.
+    pub fn is_pending(&self) -> bool {
+        self.0.ev.timer_set() != 0
+    }
+
+    /// Cancels the timer without invoking the callback.
+    pub fn cancel(self) {}
+}
+
+unsafe extern "C" fn timer_handler(ev: *mut ngx_event_t) {
+    let timer = &mut *(*ev).data.cast::<TimerEvent<Callback>>();
+
+    // The callback may drop the timer, thus it is taken out first.
+    if let Some(callback) = timer.data.take() {
+        callback();
+    }
+}
+
+/// Waits until the `duration` has elapsed.
+///
+/// The future is based on the NGINX event timers and must be polled from the worker thread, e.g.
+/// in a task created with [`spawn`](crate::async_::spawn). The time is measured with the cached
+/// time of the worker process, which is updated once per event loop iteration.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep::until(unsafe { ngx_current_msec }.wrapping_add(duration_to_msec(duration)))
+}
+
+/// A future returned by [`sleep`].
+pub struct Sleep {
+    deadline: ngx_msec_t,
+    event: Option<Box<TimerEvent<SleepState>>>,
+}
+
+#[derive(Default)]
+struct SleepState {
+    expired: bool,
+    waker: Option<Waker>,
+}
+
+impl Sleep {
+    fn until(deadline: ngx_msec_t) -> Sleep {
+        Sleep { deadline, event: None }
+    }
+
+    /// Returns `true` if the deadline has passed.
+    pub fn is_elapsed(&self) -> bool {
+        match &self.event {
+            Some(event) => event.data.expired,
+            None => remaining(self.deadline) == 0,
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+
+        let event = match &mut this.event {
+            Some(event) => event,
+            None => {
+                let timeout = remaining(this.deadline);
+                if timeout == 0 {
+                    return Poll::Ready(());
+                }
+
+                let event = this.event.insert(TimerEvent::new(SleepState::default(), sleep_handler));
+                event.add(timeout);
+                event
+            }
+        };
+
+        if event.data.expired {
+            return Poll::Ready(());
+        }
+
+        match &mut event.data.waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            waker => *waker = Some(cx.waker().clone()),
+        }
+
+        Poll::Pending
+    }
+}
+
+unsafe extern "C" fn sleep_handler(ev: *mut ngx_event_t) {
+    let event = &mut *(*ev).data.cast::<TimerEvent<SleepState>>();
+    event.data.expired = true;
+
+    if let Some(waker) = event.data.waker.take() {
+        waker.wake();
+    }
+}
+
+/// Creates an [`Interval`] that yields every `period`, with the first tick completing immediately.
+///
+/// # Panics
+///
+/// Panics if the `period` is zero.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut interval = interval(Duration::from_secs(1));
+/// for _ in 0..retries {
+///     interval.tick().await;
+///     if try_connect().is_ok() {
+///         break;
+///     }
+/// }
+/// ```
+pub fn interval(period: Duration) -> Interval {
+    let period = duration_to_msec(period);
+    assert!(period > 0, "interval period must be non-zero");
+
+    Interval {
+        period,
+        next: unsafe { ngx_current_msec },
+    }
+}
+
+/// Periodic ticks, created with [`interval`].
+///
+/// The ticks are scheduled at the fixed rate. The missed ticks, e.g. if the processing took longer
+/// than the period, complete immediately.
+#[derive(Debug)]
+pub struct Interval {
+    period: ngx_msec_t,
+    next: ngx_msec_t,
+}
+
+impl Interval {
+    /// Returns a future that completes at the next tick.
+    pub fn tick(&mut self) -> Sleep {
+        let deadline = self.next;
+        self.next = deadline.wrapping_add(self.period);
+        Sleep::until(deadline)
+    }
+
+    /// The period between the ticks.
+    pub fn period(&self) -> Duration {
+        Duration::from_millis(self.period as u64)
+    }
+}
+
+/// An event with a timer, and the data for the event handler.
+struct TimerEvent<T> {
+    ev: ngx_event_t,
+    data: T,
+}
+
+impl<T> TimerEvent<T> {
+    fn new(data: T, handler: unsafe extern "C" fn(*mut ngx_event_t)) -> Box<Self> {
+        let mut event = Box::new(TimerEvent {
+            // SAFETY: all-zero is a valid initial state of an event.
+            ev: unsafe { mem::zeroed() },
+            data,
+        });
+
+        event.ev.handler = Some(handler);
+        event.ev.log = unsafe { (*ngx_cycle).log };
+        // Do not delay the graceful shutdown.
+        event.ev.set_cancelable(1);
+
+        let data: *mut Self = &mut *event;
+        event.ev.data = data.cast();
+        event
+    }
+
+    fn add(&mut self, timer: ngx_msec_t) {
+        // SAFETY: the event is boxed and the timer is removed when the event is dropped.
+        unsafe { ngx_event_add_timer(&mut self.ev, timer) }
+    }
+}
+
+impl<T> Drop for TimerEvent<T> {
+    fn drop(&mut self) {
+        if self.ev.timer_set() != 0 {
+            unsafe { ngx_event_del_timer(&mut self.ev) }
+        }
+    }
+}
+
+/// Milliseconds until the deadline, or zero if the deadline has passed.
+fn remaining(deadline: ngx_msec_t) -> ngx_msec_t {
+    let diff = deadline.wrapping_sub(unsafe { ngx_current_msec }) as ngx_msec_int_t;
+    diff.max(0) as ngx_msec_t
+}
+
+fn duration_to_msec(duration: Duration) -> ngx_msec_t {
+    // The timers are limited to the half of the range, as the expiration time is compared with
+    // the wrapping arithmetic.
+    duration.as_millis().min(ngx_msec_int_t::MAX as u128) as ngx_msec_t
+}
@@ -0,0 +1,330 @@
+use core::cell::{Cell, RefCell, UnsafeCell};
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::ptr;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use std::task::Wake;
+use std::thread::{self, ThreadId};
+
+use crate::async_::Notifier;
+use crate::core::Status;
+use crate::ffi::*;
+use crate::http::Request;
+
+/// Spawns a future on the event loop of the current worker process.
+///
+/// The future is polled from an NGINX posted event, i.e. after the pending I/O events are
+/// processed, and is polled again each time it is woken. The returned [`Task`] resolves to the
+/// output of the future. Dropping the [`Task`] cancels the future, unless it is detached with
+/// [`Task::detach`].
+///
+/// The task can be woken from any thread. The wakeups from other threads are delivered to the
+/// worker thread with a [`Notifier`].
+pub fn spawn<F>(future: F) -> Task<F::Output>
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    let (raw, task) = RawTask::new(future);
+    raw.schedule();
+    task
+}
+
+/// Spawns a future bound to the lifetime of the request.
+///
+/// Same as [`spawn`], but the future is also cancelled when the request is freed. This allows to
+/// detach the [`Task`] without the risk of the future outliving the request. The request
+/// processing is not resumed on the completion of the future, see [`Request::suspend`].
+///
+/// Returns `Err(Status::NGX_ERROR)` if the memory cannot be allocated.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// http_request_handler!(async_access_handler, |request: &mut http::Request| {
+///     // ...
+///     let task = async_::spawn_local(request, async move {
+///         let value = fetch().await;
+///         // ...
+///     });
+///
+///     match task {
+///         Ok(task) => task.detach(),
+///         Err(status) => return status,
+///     }
+///
+///     core::Status::NGX_DONE
+/// });
+/// ```
+pub fn spawn_local<F>(request: &Request, future: F) -> Result<Task<F::Output>, Status>
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    let (raw, task) = RawTask::new(future);
+
+    if request.pool().allocate(TaskGuard(raw.clone())).is_null() {
+        return Err(Status::NGX_ERROR);
+    }
+
+    raw.schedule();
+    Ok(task)
+}
+
+/// A handle to a spawned future.
+///
+/// The task is a future resolving to the output of the spawned future.
+pub struct Task<T> {
+    raw: Rc<RawTask>,
+    output: Rc<RefCell<TaskOutput<T>>>,
+    detached: bool,
+}
+
+impl<T> Task<T> {
+    /// Lets the future run to completion without awaiting the output.
+    pub fn detach(mut self) {
+        self.detached = true;
+    }
+
+    /// Cancels the future.
+    ///
+    /// The future is dropped immediately, unless it is being polled, i.e. the task cancels itself.
+    /// In that case the future is dropped once the poll returns.
+    pub fn cancel(self) {}
+}
+
+impl<T> Drop for Task<T> {
+    fn drop(&mut self) {
+        if !self.detached {
+            self.raw.cancel();
+        }
+    }
+}
+
+impl<T> Future for Task<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut output = self.output.borrow_mut();
+
+        match output.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                output.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+struct TaskOutput<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Cancels the task when the request pool is destroyed.
+struct TaskGuard(Rc<RawTask>);
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+type LocalFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+std::thread_local! {
+    /// The spawned tasks of the worker thread, looked up by the wakers.
+    static TASKS: RefCell<HashMap<u64, Weak<RawTask>>> = RefCell::new(HashMap::new());
+}
+
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The type-erased state of a spawned future.
+struct RawTask {
+    id: u64,
+    event: UnsafeCell<ngx_event_t>,
+    future: UnsafeCell<Option<LocalFuture>>,
+    done: Cell<bool>,
+    running: Cell<bool>,
+    cancelled: Cell<bool>,
+    waker: Arc<TaskWaker>,
+}
+
+impl RawTask {
+    fn new<F>(future: F) -> (Rc<Self>, Task<F::Output>)
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let output = Rc::new(RefCell::new(TaskOutput {
+            value: None,
+            waker: None,
+        }));
+
+        let slot = output.clone();
+        let future = Box::pin(async move {
+            let value = future.await;
+
+            let waker = {
+                let mut slot = slot.borrow_mut();
+                slot.value = Some(value);
+                slot.waker.take()
+            };
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+
+        let id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+
+        let raw = Rc::new(RawTask {
+            id,
+            // SAFETY: all-zero is a valid initial state of an event.
+            event: UnsafeCell::new(unsafe { mem::zeroed() }),
+            future: UnsafeCell::new(Some(future)),
+            done: Cell::new(false),
+            running: Cell::new(false),
+            cancelled: Cell::new(false),
+            waker: Arc::new(TaskWaker {
+                id,
+                thread: thread::current().id(),
+                notifier: Notifier::new().ok(),
+            }),
+        });
+
+        TASKS.with(|tasks| tasks.borrow_mut().insert(id, Rc::downgrade(&raw)));
+
+        // SAFETY: the event is not shared yet.
+        let ev = unsafe { &mut *raw.event.get() };
+        ev.handler = Some(run_task);
+        ev.data = Rc::as_ptr(&raw).cast_mut().cast();
+        ev.log = unsafe { (*ngx_cycle).log };
+
+        let task = Task {
+            raw: raw.clone(),
+            output,
+            detached: false,
+        };
+
+        (raw, task)
+    }
+
+    /// Posts the event to poll the future.
+    fn schedule(self: &Rc<Self>) {
+        if self.done.get() {
+            return;
+        }
+
+        let ev = self.event.get();
+        unsafe {
+            if (*ev).posted() == 0 {
+                // The posted event holds a reference to the task, released in `run_task`.
+                Rc::increment_strong_count(Rc::as_ptr(self));
+                ngx_post_event(ev, ptr::addr_of_mut!(ngx_posted_events));
+            }
+        }
+    }
+
+    /// Drops the future and removes the posted event.
+    fn cancel(&self) {
+        if self.running.get() {
+            self.cancelled.set(true);
+            return;
+        }
+
+        self.finish();
+
+        let ev = self.event.get();
+        unsafe {
+            if (*ev).posted() != 0 {
+                ngx_delete_posted_event(ev);
+                // SAFETY: the caller holds another reference to the task.
+                Rc::decrement_strong_count(self as *const Self);
+            }
+        }
+    }
+
+    fn finish(&self) {
+        self.done.set(true);
+        // The future is taken out before dropping, as the drop may cancel or wake the task again.
+        let future = unsafe { (*self.future.get()).take() };
+        drop(future);
+    }
+}
+
+impl Drop for RawTask {
+    fn drop(&mut self) {
+        // The registry may be already destroyed on the thread exit.
+        let _ = TASKS.try_with(|tasks| tasks.borrow_mut().remove(&self.id));
+    }
+}
+
+/// The waker of a task, identifying the task by its id.
+///
+/// The waker does not own the task, thus it can be sent to and dropped in other threads. The
+/// wakeups from other threads are forwarded to the worker thread with the [`Notifier`], and are
+/// ignored if the notifier is not available.
+struct TaskWaker {
+    id: u64,
+    thread: ThreadId,
+    notifier: Option<Notifier>,
+}
+
+impl TaskWaker {
+    fn wake_task(id: u64) {
+        let task = TASKS.with(|tasks| tasks.borrow().get(&id).and_then(Weak::upgrade));
+        if let Some(task) = task {
+            task.schedule();
+        }
+    }
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if thread::current().id() == self.thread {
+            TaskWaker::wake_task(self.id);
+            return;
+        }
+
+        if let Some(notifier) = self.notifier {
+            let id = self.id;
+            // On failure, the wakeup is delivered with the next notification.
+            let _ = notifier.notify(move || TaskWaker::wake_task(id));
+        }
+    }
+}
+
+unsafe extern "C" fn run_task(ev: *mut ngx_event_t) {
+    // SAFETY: the reference is acquired when the event is posted.
+    let task = Rc::from_raw((*ev).data.cast::<RawTask>().cast_const());
+
+    if task.done.get() {
+        return;
+    }
+
+    let waker = Waker::from(task.waker.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    task.running.set(true);
+    let poll = match &mut *task.future.get() {
+        Some(future) => future.as_mut().poll(&mut cx),
+        None => Poll::Ready(()),
+    };
+    task.running.set(false);
+
+    if poll.is_ready() || task.cancelled.get() {
+        task.finish();
+    }
+}
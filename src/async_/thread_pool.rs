@@ -0,0 +1,215 @@
+use core::cell::RefCell;
+use core::ffi::c_void;
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+use core::task::{Context, Poll, Waker};
+use std::boxed::Box;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::rc::Rc;
+
+use crate::core::{ConfError, Status};
+use crate::ffi::*;
+use crate::http::Request;
+
+/// Handle to an NGINX thread pool, declared with the `thread_pool` directive.
+///
+/// The thread pools run the blocking work, e.g. file or database access, outside of the worker
+/// thread. Available if NGINX is built with `--with-threads`.
+///
+/// See <https://nginx.org/en/docs/ngx_core_module.html#thread_pool>
+///
+/// # Example
+///
+/// ```rust,ignore
+/// fn set_thread_pool(cf: &mut ngx_conf_t, conf: &mut LocConfig) -> Result<(), ConfError> {
+///     conf.pool = Some(ThreadPool::get(cf, "default")?);
+///     Ok(())
+/// }
+///
+/// http_request_handler!(content_handler, |request: &mut Request| {
+///     // ...
+///     let res = pool.post(request, || compute(), |request, value| {
+///         // Runs on the worker thread, with the request processing resumed afterwards.
+///     });
+///     // ...
+/// });
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ThreadPool(NonNull<ngx_thread_pool_t>);
+
+impl ThreadPool {
+    /// Gets the thread pool with the given name.
+    ///
+    /// Must be called at the configuration time, e.g. from a directive handler. The `default`
+    /// thread pool is created implicitly, and the other names must be declared with the
+    /// `thread_pool` directive, which is verified by NGINX at the end of the configuration parsing.
+    pub fn get(cf: &mut ngx_conf_t, name: &str) -> Result<Self, ConfError> {
+        // NGINX keeps the reference to the name.
+        let Some(mut name) = (unsafe { ngx_str_t::from_bytes(cf.pool, name.as_bytes()) }) else {
+            return Err(ConfError::Message(c"cannot allocate memory"));
+        };
+
+        let tp = unsafe { ngx_thread_pool_add(cf, &mut name) };
+        NonNull::new(tp)
+            .map(ThreadPool)
+            .ok_or(ConfError::Message(c"cannot add thread pool"))
+    }
+
+    /// Runs the `work` in the thread pool, and then the `complete` callback with the result on
+    /// the worker thread.
+    ///
+    /// As with the thread-based I/O in NGINX, the request is blocked from being freed and marked
+    /// as having a pending asynchronous operation until the work is complete. Once the `complete`
+    /// callback returns, the request processing is resumed with the write event handler of the
+    /// request. E.g. the phase handler that posted the work is invoked again, and can return the
+    /// result stored in the request context.
+    ///
+    /// The completion is delivered to the worker thread by the thread pool itself, with
+    /// `ngx_notify`.
+    ///
+    /// A panic in the `work` closure aborts the worker process.
+    pub fn post<W, T, C>(&self, request: &mut Request, work: W, complete: C) -> Result<(), Status>
+    where
+        W: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+        C: FnOnce(&mut Request, T) + 'static,
+    {
+        let r: *mut ngx_http_request_t = request.into();
+
+        let task = unsafe { ngx_thread_task_alloc((*r).pool, 0) };
+        let Some(task) = (unsafe { task.as_mut() }) else {
+            return Err(Status::NGX_ERROR);
+        };
+
+        let ctx = Box::into_raw(Box::new(TaskCtx::<W, T, C> {
+            work: Some(work),
+            result: None,
+            complete: Some(complete),
+            request: r,
+        }));
+
+        task.ctx = ctx.cast();
+        task.handler = Some(thread_handler::<W, T, C>);
+        task.event.data = ctx.cast();
+        task.event.handler = Some(complete_handler::<W, T, C>);
+        task.event.log = unsafe { (*(*r).connection).log };
+
+        if unsafe { ngx_thread_task_post(self.0.as_ptr(), task) } != Status::NGX_OK.into() {
+            drop(unsafe { Box::from_raw(ctx) });
+            return Err(Status::NGX_ERROR);
+        }
+
+        unsafe {
+            let main = &mut *(*r).main;
+            main.set_blocked(main.blocked() + 1);
+            (*r).set_aio(1);
+        }
+
+        Ok(())
+    }
+
+    /// Runs the `work` in the thread pool, returning a future that resolves to the result.
+    ///
+    /// The request processing is resumed on completion in the same way as with
+    /// [`ThreadPool::post`]. Dropping the returned future discards the result, but does not cancel
+    /// the work.
+    pub fn spawn<W, T>(&self, request: &mut Request, work: W) -> Result<ThreadTask<T>, Status>
+    where
+        W: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let output = Rc::new(RefCell::new(TaskOutput {
+            value: None,
+            waker: None,
+        }));
+
+        let slot = output.clone();
+        self.post(request, work, move |_, value| {
+            let waker = {
+                let mut slot = slot.borrow_mut();
+                slot.value = Some(value);
+                slot.waker.take()
+            };
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        })?;
+
+        Ok(ThreadTask { output })
+    }
+}
+
+/// A future resolving to the result of the work spawned with [`ThreadPool::spawn`].
+pub struct ThreadTask<T> {
+    output: Rc<RefCell<TaskOutput<T>>>,
+}
+
+struct TaskOutput<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+}
+
+impl<T> Future for ThreadTask<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut output = self.output.borrow_mut();
+
+        match output.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                output.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+struct TaskCtx<W, T, C> {
+    work: Option<W>,
+    result: Option<T>,
+    complete: Option<C>,
+    request: *mut ngx_http_request_t,
+}
+
+unsafe extern "C" fn thread_handler<W, T, C>(data: *mut c_void, _log: *mut ngx_log_t)
+where
+    W: FnOnce() -> T,
+{
+    // Only the `Send` fields of the context are accessed in the pool thread.
+    let ctx = data.cast::<TaskCtx<W, T, C>>();
+
+    if let Some(work) = (*ptr::addr_of_mut!((*ctx).work)).take() {
+        // Unwinding out of an `extern "C"` function is undefined behavior.
+        match panic::catch_unwind(AssertUnwindSafe(work)) {
+            Ok(result) => *ptr::addr_of_mut!((*ctx).result) = Some(result),
+            Err(_) => process::abort(),
+        }
+    }
+}
+
+unsafe extern "C" fn complete_handler<W, T, C>(ev: *mut ngx_event_t)
+where
+    C: FnOnce(&mut Request, T),
+{
+    let mut ctx = Box::from_raw((*ev).data.cast::<TaskCtx<W, T, C>>());
+    let r = ctx.request;
+    let c = (*r).connection;
+
+    let main = &mut *(*r).main;
+    main.set_blocked(main.blocked() - 1);
+    (*r).set_aio(0);
+
+    if let (Some(complete), Some(result)) = (ctx.complete.take(), ctx.result.take()) {
+        complete(Request::from_ngx_http_request(r), result);
+    }
+    drop(ctx);
+
+    if let Some(handler) = (*r).write_event_handler {
+        handler(r);
+    }
+    ngx_http_run_posted_requests(c);
+}
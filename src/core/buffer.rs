@@ -138,3 +138,58 @@ impl Buffer for MemoryBuffer {
         self.0
     }
 }
+
+/// Wrapper struct for a file buffer, providing methods for working with an `ngx_buf_t` that refers
+/// to a region of a file.
+pub struct FileBuffer(*mut ngx_buf_t);
+
+impl FileBuffer {
+    /// Creates a new `FileBuffer` from an `ngx_buf_t` pointer.
+    ///
+    /// # Panics
+    /// Panics if the given buffer pointer is null.
+    pub fn from_ngx_buf(buf: *mut ngx_buf_t) -> FileBuffer {
+        assert!(!buf.is_null());
+        FileBuffer(buf)
+    }
+
+    /// Returns the file the buffer refers to.
+    pub fn file(&self) -> &ngx_file_t {
+        unsafe { &*(*self.0).file }
+    }
+
+    /// Returns the offset of the buffer contents in the file.
+    pub fn file_pos(&self) -> off_t {
+        unsafe { (*self.0).file_pos }
+    }
+
+    /// Returns the length of the buffer contents.
+    pub fn len(&self) -> usize {
+        let buf = unsafe { &*self.0 };
+        assert!(buf.file_last >= buf.file_pos);
+        (buf.file_last - buf.file_pos) as usize
+    }
+
+    /// Returns `true` if the buffer is empty, i.e., it has zero length.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads the buffer contents starting at `offset` from the beginning of the buffer into `dst`.
+    ///
+    /// Returns the number of bytes read, or `None` if the read failed. The error is logged to the
+    /// file log.
+    pub fn read(&self, dst: &mut [u8], offset: usize) -> Option<usize> {
+        let buf = unsafe { &*self.0 };
+        let size = dst.len().min(self.len().saturating_sub(offset));
+        if size == 0 {
+            return Some(0);
+        }
+
+        let n = unsafe { ngx_read_file(buf.file, dst.as_mut_ptr(), size, buf.file_pos + offset as off_t) };
+        if n < 0 {
+            return None;
+        }
+        Some(n as usize)
+    }
+}
@@ -0,0 +1,112 @@
+use core::ffi::{c_char, c_void};
+use core::ptr;
+
+use crate::core::ConfError;
+use crate::ffi::*;
+
+/// Defines a static table of configuration directives for the module type.
+///
+/// The implementation of [`http_commands`](crate::http_commands),
+/// [`stream_commands`](crate::stream_commands) and [`mail_commands`](crate::mail_commands).
+#[doc(hidden)]
+#[macro_export]
+macro_rules! ngx_commands {
+    (@context http main) => { $crate::ffi::NGX_HTTP_MAIN_CONF };
+    (@context http srv) => { $crate::ffi::NGX_HTTP_SRV_CONF };
+    (@context http loc) => { $crate::ffi::NGX_HTTP_LOC_CONF };
+    (@context http ups) => { $crate::ffi::NGX_HTTP_UPS_CONF };
+    (@context http sif) => { $crate::ffi::NGX_HTTP_SIF_CONF };
+    (@context http lif) => { $crate::ffi::NGX_HTTP_LIF_CONF };
+    (@context http lmt) => { $crate::ffi::NGX_HTTP_LMT_CONF };
+    (@context stream main) => { $crate::ffi::NGX_STREAM_MAIN_CONF };
+    (@context stream srv) => { $crate::ffi::NGX_STREAM_SRV_CONF };
+    (@context stream ups) => { $crate::ffi::NGX_STREAM_UPS_CONF };
+    (@context mail main) => { $crate::ffi::NGX_MAIL_MAIN_CONF };
+    (@context mail srv) => { $crate::ffi::NGX_MAIL_SRV_CONF };
+
+    (@args 0) => { $crate::ffi::NGX_CONF_NOARGS };
+    (@args 1) => { $crate::ffi::NGX_CONF_TAKE1 };
+    (@args 2) => { $crate::ffi::NGX_CONF_TAKE2 };
+    (@args 3) => { $crate::ffi::NGX_CONF_TAKE3 };
+    (@args 4) => { $crate::ffi::NGX_CONF_TAKE4 };
+    (@args 5) => { $crate::ffi::NGX_CONF_TAKE5 };
+    (@args 6) => { $crate::ffi::NGX_CONF_TAKE6 };
+    (@args 7) => { $crate::ffi::NGX_CONF_TAKE7 };
+    (@args flag) => { $crate::ffi::NGX_CONF_FLAG };
+    (@args any) => { $crate::ffi::NGX_CONF_ANY };
+    (@args one_or_more) => { $crate::ffi::NGX_CONF_1MORE };
+    (@args two_or_more) => { $crate::ffi::NGX_CONF_2MORE };
+    (@args block) => { $crate::ffi::NGX_CONF_BLOCK };
+
+    (@conf http main) => { $crate::ffi::NGX_HTTP_MAIN_CONF_OFFSET };
+    (@conf http srv) => { $crate::ffi::NGX_HTTP_SRV_CONF_OFFSET };
+    (@conf http loc) => { $crate::ffi::NGX_HTTP_LOC_CONF_OFFSET };
+    (@conf stream main) => { $crate::stream::NGX_STREAM_MAIN_CONF_OFFSET };
+    (@conf stream srv) => { $crate::stream::NGX_STREAM_SRV_CONF_OFFSET };
+    (@conf mail main) => { $crate::mail::NGX_MAIL_MAIN_CONF_OFFSET };
+    (@conf mail srv) => { $crate::mail::NGX_MAIL_SRV_CONF_OFFSET };
+
+    (
+        $module:ident;
+        $( #[$attr:meta] )*
+        $vis:vis static mut $table:ident = [
+            $(
+                $name:literal => {
+                    context: $( $context:ident )|+,
+                    args: $( $args:tt )|+,
+                    conf: $conf:ident,
+                    set: $set:expr $(,)?
+                }
+            ),+ $(,)?
+        ];
+    ) => {
+        $( #[$attr] )*
+        $vis static mut $table: [$crate::ffi::ngx_command_t; $crate::count!($( $name, )+) + 1] = [
+            $(
+                $crate::ffi::ngx_command_t {
+                    name: $crate::ngx_string!($name),
+                    type_: ($( $crate::ngx_commands!(@context $module $context) )|+
+                        | $( $crate::ngx_commands!(@args $args) )|+) as $crate::ffi::ngx_uint_t,
+                    set: {
+                        unsafe extern "C" fn set(
+                            cf: *mut $crate::ffi::ngx_conf_t,
+                            cmd: *mut $crate::ffi::ngx_command_t,
+                            conf: *mut ::core::ffi::c_void,
+                        ) -> *mut ::core::ffi::c_char {
+                            $crate::core::command_handler(cf, cmd, conf, $set)
+                        }
+                        Some(set)
+                    },
+                    conf: $crate::ngx_commands!(@conf $module $conf),
+                    offset: 0,
+                    post: ::core::ptr::null_mut(),
+                },
+            )+
+            $crate::ngx_null_command!(),
+        ];
+    };
+}
+
+/// Invokes a configuration directive handler defined with [`ngx_commands`](crate::ngx_commands).
+///
+/// # Safety
+///
+/// The caller has provided valid non-null `ngx_conf_t` and `ngx_command_t` pointers, and `conf`
+/// points to a valid configuration of type `T` for the current module.
+#[doc(hidden)]
+pub unsafe fn command_handler<T, F>(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+    set: F,
+) -> *mut c_char
+where
+    F: FnOnce(&mut ngx_conf_t, &mut T) -> Result<(), ConfError>,
+{
+    let cf = &mut *cf;
+    let conf = &mut *conf.cast::<T>();
+    match set(cf, conf) {
+        Ok(_) => ptr::null_mut(),
+        Err(err) => err.into(),
+    }
+}
@@ -0,0 +1,192 @@
+use core::ffi::{c_char, CStr};
+use core::fmt;
+
+use crate::core::NGX_CONF_ERROR;
+use crate::ffi::*;
+use crate::{ngx_conf_log_error, ngx_null_string};
+
+/// Error returned from a configuration directive handler.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#config_directives>
+#[derive(Debug)]
+pub enum ConfError {
+    /// The error has already been reported with [`ngx_conf_log_error`](crate::ngx_conf_log_error).
+    ///
+    /// The handler will return `NGX_CONF_ERROR`.
+    Reported,
+    /// The error message to be reported by NGINX.
+    ///
+    /// NGINX logs the message with the current configuration file and line as
+    /// `"<directive>" directive <message>`.
+    Message(&'static CStr),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConfError {}
+
+impl fmt::Display for ConfError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfError::Reported => "configuration error".fmt(fmt),
+            ConfError::Message(msg) => msg.to_str().unwrap_or("invalid message").fmt(fmt),
+        }
+    }
+}
+
+impl From<ConfError> for *mut c_char {
+    fn from(val: ConfError) -> Self {
+        match val {
+            ConfError::Reported => NGX_CONF_ERROR as _,
+            ConfError::Message(msg) => msg.as_ptr().cast_mut(),
+        }
+    }
+}
+
+/// Parses a directive argument as a boolean flag, `on` or `off`.
+///
+/// The argument `n` is the index of the value in [`ngx_conf_t::args`], where `0` is the directive
+/// name itself. All the `parse_*` functions report an error if there is no such argument.
+///
+/// Mirrors `ngx_conf_set_flag_slot`.
+pub fn parse_flag(cf: &mut ngx_conf_t, n: usize) -> Result<bool, ConfError> {
+    let value = conf_arg(cf, n)?;
+
+    if value.as_bytes().eq_ignore_ascii_case(b"on") {
+        Ok(true)
+    } else if value.as_bytes().eq_ignore_ascii_case(b"off") {
+        Ok(false)
+    } else {
+        Err(invalid_value(cf, value, ", it must be \"on\" or \"off\""))
+    }
+}
+
+/// Parses a directive argument as a non-negative integer.
+///
+/// Mirrors `ngx_conf_set_num_slot`.
+pub fn parse_number(cf: &mut ngx_conf_t, n: usize) -> Result<ngx_int_t, ConfError> {
+    let value = conf_arg(cf, n)?;
+
+    match unsafe { ngx_atoi(value.data, value.len) } {
+        rc if rc == NGX_ERROR as ngx_int_t => Err(ConfError::Message(c"invalid number")),
+        rc => Ok(rc),
+    }
+}
+
+/// Parses a directive argument as a size with an optional `k` or `m` suffix.
+///
+/// Mirrors `ngx_conf_set_size_slot`.
+pub fn parse_size(cf: &mut ngx_conf_t, n: usize) -> Result<usize, ConfError> {
+    let mut value = conf_arg(cf, n)?;
+
+    match unsafe { ngx_parse_size(&mut value) } {
+        rc if rc == NGX_ERROR as _ => Err(ConfError::Message(c"invalid value")),
+        rc => Ok(rc as usize),
+    }
+}
+
+/// Parses a directive argument as an offset with an optional `k`, `m` or `g` suffix.
+///
+/// Mirrors `ngx_conf_set_off_slot`.
+pub fn parse_offset(cf: &mut ngx_conf_t, n: usize) -> Result<off_t, ConfError> {
+    let mut value = conf_arg(cf, n)?;
+
+    match unsafe { ngx_parse_offset(&mut value) } {
+        rc if rc == NGX_ERROR as _ => Err(ConfError::Message(c"invalid value")),
+        rc => Ok(rc),
+    }
+}
+
+/// Parses a directive argument as a time interval in milliseconds, e.g. `1s` or `500ms`.
+///
+/// Mirrors `ngx_conf_set_msec_slot`.
+pub fn parse_msec(cf: &mut ngx_conf_t, n: usize) -> Result<ngx_msec_t, ConfError> {
+    let mut value = conf_arg(cf, n)?;
+
+    match unsafe { ngx_parse_time(&mut value, 0) } {
+        rc if rc == NGX_ERROR as ngx_int_t => Err(ConfError::Message(c"invalid value")),
+        rc => Ok(rc as ngx_msec_t),
+    }
+}
+
+/// Parses a directive argument as a time interval in seconds, e.g. `1m` or `30s`.
+///
+/// Mirrors `ngx_conf_set_sec_slot`.
+pub fn parse_sec(cf: &mut ngx_conf_t, n: usize) -> Result<time_t, ConfError> {
+    let mut value = conf_arg(cf, n)?;
+
+    match unsafe { ngx_parse_time(&mut value, 1) } {
+        rc if rc == NGX_ERROR as ngx_int_t => Err(ConfError::Message(c"invalid value")),
+        rc => Ok(rc as time_t),
+    }
+}
+
+/// Parses a directive argument as one of the named values.
+///
+/// The names are matched case-insensitively. Mirrors `ngx_conf_set_enum_slot`.
+pub fn parse_enum<T: Copy>(cf: &mut ngx_conf_t, n: usize, values: &[(&str, T)]) -> Result<T, ConfError> {
+    let value = conf_arg(cf, n)?;
+
+    for (name, val) in values {
+        if value.as_bytes().eq_ignore_ascii_case(name.as_bytes()) {
+            return Ok(*val);
+        }
+    }
+
+    Err(invalid_value(cf, value, ""))
+}
+
+/// Parses a directive argument as a file system path.
+///
+/// Relative paths are resolved against the NGINX prefix. Mirrors the path handling of
+/// `ngx_conf_set_path_slot`.
+pub fn parse_path(cf: &mut ngx_conf_t, n: usize) -> Result<ngx_str_t, ConfError> {
+    let mut value = conf_arg(cf, n)?;
+
+    if unsafe { ngx_conf_full_name(cf.cycle, &mut value, 0) } != NGX_OK as ngx_int_t {
+        let name = directive_name(cf);
+        ngx_conf_log_error!(
+            NGX_LOG_EMERG,
+            cf,
+            "failed to resolve path \"{}\" in \"{}\" directive",
+            value,
+            name
+        );
+        return Err(ConfError::Reported);
+    }
+
+    Ok(value)
+}
+
+/// Returns the directive argument `n`, or reports an error if there is no such argument.
+pub(crate) fn conf_arg(cf: &mut ngx_conf_t, n: usize) -> Result<ngx_str_t, ConfError> {
+    if let Some(value) = cf.args().get(n) {
+        return Ok(*value);
+    }
+
+    let name = directive_name(cf);
+    ngx_conf_log_error!(
+        NGX_LOG_EMERG,
+        cf,
+        "invalid number of arguments in \"{}\" directive",
+        name
+    );
+    Err(ConfError::Reported)
+}
+
+/// Reports an invalid directive argument, in the same format as the NGINX directive handlers.
+fn invalid_value(cf: &mut ngx_conf_t, value: ngx_str_t, hint: &str) -> ConfError {
+    let name = directive_name(cf);
+    ngx_conf_log_error!(
+        NGX_LOG_EMERG,
+        cf,
+        "invalid value \"{}\" in \"{}\" directive{}",
+        value,
+        name,
+        hint
+    );
+    ConfError::Reported
+}
+
+fn directive_name(cf: &ngx_conf_t) -> ngx_str_t {
+    cf.args().first().copied().unwrap_or(ngx_null_string!())
+}
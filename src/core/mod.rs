@@ -1,12 +1,18 @@
 mod buffer;
+mod command;
+mod conf;
 mod pool;
 mod status;
 mod string;
+mod variable;
 
 pub use buffer::*;
+pub use command::*;
+pub use conf::*;
 pub use pool::*;
 pub use status::*;
 pub use string::*;
+pub use variable::*;
 
 /// Static empty configuration directive initializer for [`ngx_command_t`].
 ///
@@ -0,0 +1,297 @@
+use core::ops::BitOr;
+use core::slice;
+
+use crate::core::{NgxStr, Pool, Status};
+use crate::ffi::*;
+
+/// Flags of an HTTP or stream variable.
+///
+/// The values of the `NGX_HTTP_VAR_*` and the `NGX_STREAM_VAR_*` flags are the same.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#http_variables>
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VariableFlags(ngx_uint_t);
+
+impl VariableFlags {
+    /// No flags.
+    pub const NONE: Self = Self(0);
+    /// `NGX_HTTP_VAR_CHANGEABLE` - the variable can be redefined with the `set` directive.
+    pub const CHANGEABLE: Self = Self(NGX_HTTP_VAR_CHANGEABLE as _);
+    /// `NGX_HTTP_VAR_NOCACHEABLE` - the value is not cached and is evaluated on each access.
+    pub const NOCACHEABLE: Self = Self(NGX_HTTP_VAR_NOCACHEABLE as _);
+    /// `NGX_HTTP_VAR_NOHASH` - the variable is only accessible by index, not by name.
+    pub const NOHASH: Self = Self(NGX_HTTP_VAR_NOHASH as _);
+    /// `NGX_HTTP_VAR_PREFIX` - the name is a prefix, e.g. `sni_`, and the getter is invoked for
+    /// all the variables starting with it. See [`ModuleVariable::prefix_getter`].
+    pub const PREFIX: Self = Self(NGX_HTTP_VAR_PREFIX as _);
+
+    /// Returns the raw flags value.
+    pub const fn bits(&self) -> ngx_uint_t {
+        self.0
+    }
+
+    /// Checks if all the flags in `other` are set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for VariableFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The object the variables are evaluated for, i.e. an HTTP request or a stream session.
+pub trait VariableContext: Sized + 'static {
+    /// The NGINX type passed to the variable handlers, e.g. [`ngx_http_request_t`].
+    type Raw;
+
+    /// Creates a reference to the context from the pointer passed to the variable handlers.
+    ///
+    /// # Safety
+    ///
+    /// The caller has provided a valid non-null pointer to the NGINX object.
+    unsafe fn from_raw<'a>(raw: *mut Self::Raw) -> &'a mut Self;
+
+    /// Finds the prefix variable registered by the crate for the full variable name.
+    ///
+    /// NGINX passes the requested name to the getters of the prefix variables instead of the
+    /// `data` value set at the registration, so the definition is looked up in the prefix
+    /// variables of the core module.
+    fn prefix_variable(&self, name: &[u8]) -> Option<&'static ModuleVariable<Self>>;
+}
+
+type Getter<C> = for<'a> fn(&'a mut C) -> Result<Option<&'a [u8]>, Status>;
+type PrefixGetter<C> = for<'a, 'n> fn(&'a mut C, &'n NgxStr) -> Result<Option<&'a [u8]>, Status>;
+type Setter<C> = for<'a> fn(&'a mut C, &'a NgxStr);
+
+/// Definition of a variable provided by a module.
+///
+/// See [`http::Variable`](crate::http::Variable) and `stream::Variable`.
+pub struct ModuleVariable<C> {
+    pub(crate) name: &'static str,
+    pub(crate) flags: VariableFlags,
+    pub(crate) get: Option<Getter<C>>,
+    pub(crate) prefix_get: Option<PrefixGetter<C>>,
+    pub(crate) set: Option<Setter<C>>,
+}
+
+impl<C> ModuleVariable<C> {
+    /// Creates a variable definition with the given name, without the leading `$`.
+    pub const fn new(name: &'static str) -> Self {
+        ModuleVariable {
+            name,
+            flags: VariableFlags::NONE,
+            get: None,
+            prefix_get: None,
+            set: None,
+        }
+    }
+
+    /// Sets the variable flags.
+    pub const fn flags(mut self, flags: VariableFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the variable getter.
+    ///
+    /// The getter returns the value of the variable, or `None` if the variable is not found. The
+    /// returned slice must remain valid for the lifetime of the request or the session, e.g. point
+    /// to its data or be allocated from its pool. An error, e.g. a failed allocation, fails the
+    /// evaluation of the variable with `NGX_ERROR`.
+    pub const fn getter(mut self, get: Getter<C>) -> Self {
+        self.get = Some(get);
+        self
+    }
+
+    /// Sets the getter of a prefix variable and adds the [`VariableFlags::PREFIX`] flag.
+    ///
+    /// The getter is invoked for all the variables starting with the name of this variable, with
+    /// the rest of the requested name, e.g. `example.com` for `$sni_example.com` with the `sni_`
+    /// prefix. The return value is the same as for [`ModuleVariable::getter`]. The setters are not
+    /// invoked for the prefix variables.
+    pub const fn prefix_getter(mut self, get: PrefixGetter<C>) -> Self {
+        self.flags = VariableFlags(self.flags.0 | VariableFlags::PREFIX.0);
+        self.prefix_get = Some(get);
+        self
+    }
+
+    /// Sets the variable setter, invoked for the changeable variables by the `set` directive.
+    pub const fn setter(mut self, set: Setter<C>) -> Self {
+        self.set = Some(set);
+        self
+    }
+
+    /// Returns the variable name.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The name of the variable as [`ngx_str_t`].
+    pub(crate) fn ngx_name(&self) -> ngx_str_t {
+        ngx_str_t {
+            len: self.name.len(),
+            data: self.name.as_ptr().cast_mut(),
+        }
+    }
+
+    /// The flags passed to NGINX on the registration.
+    pub(crate) fn ngx_flags(&self) -> ngx_uint_t {
+        match self.prefix_get {
+            Some(_) => (self.flags | VariableFlags::PREFIX).bits(),
+            None => self.flags.bits(),
+        }
+    }
+
+    /// Is the variable registered as a prefix variable?
+    pub(crate) fn is_prefix(&self) -> bool {
+        self.prefix_get.is_some() || self.flags.contains(VariableFlags::PREFIX)
+    }
+}
+
+/// The `get_handler` of the variables registered from a [`ModuleVariable`].
+///
+/// # Safety
+///
+/// The `data` must be set to a static [`ModuleVariable<C>`] reference.
+pub(crate) unsafe extern "C" fn variable_get_handler<C: VariableContext>(
+    raw: *mut C::Raw,
+    v: *mut ngx_variable_value_t,
+    data: usize,
+) -> ngx_int_t {
+    let var = &*(data as *const ModuleVariable<C>);
+    let ctx = C::from_raw(raw);
+
+    match var.get {
+        Some(get) => set_variable_value(&mut *v, get(ctx)),
+        None => set_variable_value(&mut *v, Ok(None)),
+    }
+}
+
+/// The `get_handler` of the prefix variables registered from a [`ModuleVariable`].
+///
+/// # Safety
+///
+/// The `data` must be set by NGINX to the requested variable name, as for all the prefix variables.
+pub(crate) unsafe extern "C" fn variable_prefix_get_handler<C: VariableContext>(
+    raw: *mut C::Raw,
+    v: *mut ngx_variable_value_t,
+    data: usize,
+) -> ngx_int_t {
+    let name: &[u8] = (*(data as *const ngx_str_t)).into();
+    let ctx = C::from_raw(raw);
+
+    let Some(var) = ctx.prefix_variable(name) else {
+        return Status::NGX_ERROR.into();
+    };
+
+    let value = match (var.prefix_get, var.get) {
+        (Some(get), _) => get(ctx, name[var.name.len()..].into()),
+        (None, Some(get)) => get(ctx),
+        (None, None) => Ok(None),
+    };
+    set_variable_value(&mut *v, value)
+}
+
+/// Fills the variable value with the result of a getter.
+fn set_variable_value(v: &mut ngx_variable_value_t, value: Result<Option<&[u8]>, Status>) -> ngx_int_t {
+    match value {
+        Ok(Some(value)) => {
+            v.set_len(value.len() as _);
+            v.data = value.as_ptr().cast_mut();
+            v.set_valid(1);
+            v.set_no_cacheable(0);
+            v.set_not_found(0);
+        }
+        Ok(None) => v.set_not_found(1),
+        Err(_) => return Status::NGX_ERROR.into(),
+    }
+
+    Status::NGX_OK.into()
+}
+
+/// Finds the prefix variable with the `handler` matching the full variable name, in the order
+/// NGINX looks up the prefix variables.
+///
+/// The items are the name, the `get_handler` and the `data` of the registered prefix variables.
+pub(crate) fn find_prefix_variable<'a, C>(
+    variables: impl Iterator<Item = (&'a ngx_str_t, *const (), usize)>,
+    handler: *const (),
+    name: &[u8],
+) -> Option<&'static ModuleVariable<C>> {
+    for (prefix, get_handler, data) in variables {
+        let prefix: &[u8] = (*prefix).into();
+        if !name.starts_with(prefix) {
+            continue;
+        }
+        // The first matching prefix variable is the one NGINX invoked.
+        if get_handler != handler || data == 0 {
+            return None;
+        }
+        // SAFETY: the data of the variables with the handler is set to a static reference.
+        return Some(unsafe { &*(data as *const ModuleVariable<C>) });
+    }
+    None
+}
+
+/// The `set_handler` of the variables registered from a [`ModuleVariable`].
+///
+/// # Safety
+///
+/// The `data` must be set to a static [`ModuleVariable<C>`] reference.
+pub(crate) unsafe extern "C" fn variable_set_handler<C: VariableContext>(
+    raw: *mut C::Raw,
+    v: *mut ngx_variable_value_t,
+    data: usize,
+) {
+    let var = &*(data as *const ModuleVariable<C>);
+    let ctx = C::from_raw(raw);
+    let v = &*v;
+
+    if let (Some(set), Some(value)) = (var.set, variable_value(v)) {
+        set(ctx, value);
+    }
+}
+
+/// Converts the result of a variable lookup to a string.
+pub(crate) fn variable_value<'a>(v: *const ngx_variable_value_t) -> Option<&'a NgxStr> {
+    let v = unsafe { v.as_ref()? };
+    if v.not_found() != 0 {
+        return None;
+    }
+    if v.len() == 0 {
+        return Some((&[][..]).into());
+    }
+    Some(unsafe { slice::from_raw_parts(v.data, v.len() as usize) }.into())
+}
+
+/// Hashes the lowercase copy of the variable name, as expected by the variable lookup by name.
+pub(crate) fn variable_key(mut pool: Pool, name: &str) -> Option<(ngx_str_t, ngx_uint_t)> {
+    let data = pool.alloc_unaligned(name.len()).cast::<u8>();
+    if data.is_null() {
+        return None;
+    }
+    let key = unsafe { ngx_hash_strlow(data, name.as_ptr().cast_mut(), name.len()) };
+    Some((ngx_str_t { len: name.len(), data }, key))
+}
+
+/// Copies the value to the pool, for the values returned from the variable getters.
+pub(crate) fn alloc_str<'a>(mut pool: Pool, value: &[u8]) -> Result<&'a [u8], Status> {
+    if value.is_empty() {
+        return Ok(&[]);
+    }
+
+    let data = pool.alloc_unaligned(value.len()).cast::<u8>();
+    if data.is_null() {
+        return Err(Status::NGX_ERROR);
+    }
+
+    unsafe {
+        data.copy_from_nonoverlapping(value.as_ptr(), value.len());
+        Ok(slice::from_raw_parts(data, value.len()))
+    }
+}
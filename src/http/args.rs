@@ -0,0 +1,196 @@
+use core::slice;
+
+use crate::core::{NgxStr, Pool};
+use crate::ffi::*;
+use crate::http::Request;
+
+impl Request {
+    /// Raw query string of the request URI, without the leading `?`.
+    pub fn args(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.get_inner().args) }
+    }
+
+    /// Value of the query string argument `name`, without percent-decoding.
+    ///
+    /// The argument name is matched case-insensitively, as with the `$arg_name` variables.
+    pub fn arg(&self, name: &str) -> Option<&NgxStr> {
+        let r: *const ngx_http_request_t = self.into();
+        let mut value = ngx_str_t {
+            len: 0,
+            data: core::ptr::null_mut(),
+        };
+
+        let rc = unsafe { ngx_http_arg(r.cast_mut(), name.as_ptr().cast_mut(), name.len(), &mut value) };
+        if rc != NGX_OK as ngx_int_t {
+            return None;
+        }
+
+        Some(unsafe { NgxStr::from_ngx_str(value) })
+    }
+
+    /// Returns an iterator over the query string arguments, without percent-decoding.
+    pub fn args_iter(&self) -> ArgsIterator<'_> {
+        ArgsIterator {
+            rest: self.args().as_bytes(),
+        }
+    }
+
+    /// Returns an iterator over the query string arguments, with percent-decoding.
+    ///
+    /// The names and values are decoded with `ngx_unescape_uri` into the request pool, and `+` is
+    /// decoded as a space. As in NGINX, the `%` of an invalid percent-encoded sequence is dropped,
+    /// e.g. `%zz` is decoded as `zz`. The iteration stops if the memory allocation fails.
+    pub fn decoded_args(&self) -> DecodedArgsIterator<'_> {
+        DecodedArgsIterator {
+            args: self.args_iter(),
+            pool: self.pool(),
+        }
+    }
+}
+
+/// Iterator over the query string arguments.
+///
+/// Yields `(name, value)` pairs. The value is empty if the argument has no `=` sign.
+pub struct ArgsIterator<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for ArgsIterator<'a> {
+    type Item = (&'a NgxStr, &'a NgxStr);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.rest.is_empty() {
+            let (arg, rest) = match self.rest.iter().position(|&c| c == b'&') {
+                Some(i) => (&self.rest[..i], &self.rest[i + 1..]),
+                None => (self.rest, &[][..]),
+            };
+            self.rest = rest;
+
+            if arg.is_empty() {
+                continue;
+            }
+
+            return Some(match arg.iter().position(|&c| c == b'=') {
+                Some(i) => (arg[..i].into(), arg[i + 1..].into()),
+                None => (arg.into(), (&[][..]).into()),
+            });
+        }
+
+        None
+    }
+}
+
+/// Iterator over the percent-decoded query string arguments.
+///
+/// See [`Request::decoded_args`].
+pub struct DecodedArgsIterator<'a> {
+    args: ArgsIterator<'a>,
+    pool: Pool,
+}
+
+impl<'a> Iterator for DecodedArgsIterator<'a> {
+    type Item = (&'a NgxStr, &'a NgxStr);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (name, value) = self.args.next()?;
+        Some((unescape(&mut self.pool, name)?, unescape(&mut self.pool, value)?))
+    }
+}
+
+/// Decodes the argument with `ngx_unescape_uri` into a copy allocated from the pool.
+fn unescape<'a>(pool: &mut Pool, src: &NgxStr) -> Option<&'a NgxStr> {
+    let src = src.as_bytes();
+    if src.is_empty() {
+        return Some((&[][..]).into());
+    }
+
+    let data = pool.alloc_unaligned(src.len()).cast::<u8>();
+    if data.is_null() {
+        return None;
+    }
+
+    // SAFETY: `data` is a valid allocation of `src.len()` bytes. The decoding is done in place, as
+    // the result is never longer than the input.
+    unsafe {
+        plus_to_space(src, slice::from_raw_parts_mut(data, src.len()));
+
+        let mut dst = data;
+        let mut s = data;
+        ngx_unescape_uri(&mut dst, &mut s, src.len(), 0);
+
+        Some(slice::from_raw_parts(data, dst.offset_from(data) as usize).into())
+    }
+}
+
+/// Copies the argument to `dst`, replacing `+` with a space, which `ngx_unescape_uri` does not
+/// decode.
+fn plus_to_space(src: &[u8], dst: &mut [u8]) {
+    for (dst, &c) in dst.iter_mut().zip(src) {
+        *dst = if c == b'+' { b' ' } else { c };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Pair<'a> = (&'a [u8], &'a [u8]);
+
+    fn args(query: &str) -> ([Pair<'_>; 8], usize) {
+        let mut out = [(&b""[..], &b""[..]); 8];
+        let mut n = 0;
+        for (name, value) in (ArgsIterator { rest: query.as_bytes() }) {
+            out[n] = (name.as_bytes(), value.as_bytes());
+            n += 1;
+        }
+        (out, n)
+    }
+
+    fn spaces<'b>(src: &str, buf: &'b mut [u8]) -> &'b [u8] {
+        plus_to_space(src.as_bytes(), buf);
+        &buf[..src.len()]
+    }
+
+    #[test]
+    fn args_iterator() {
+        let (out, n) = args("a=1&b=2");
+        assert_eq!(out[..n], [(&b"a"[..], &b"1"[..]), (&b"b"[..], &b"2"[..])]);
+
+        let (out, n) = args("a=b=c");
+        assert_eq!(out[..n], [(&b"a"[..], &b"b=c"[..])]);
+    }
+
+    #[test]
+    fn args_iterator_empty_pairs() {
+        assert_eq!(args("").1, 0);
+        assert_eq!(args("&").1, 0);
+        assert_eq!(args("&&&").1, 0);
+
+        let (out, n) = args("a&&b");
+        assert_eq!(out[..n], [(&b"a"[..], &b""[..]), (&b"b"[..], &b""[..])]);
+
+        let (out, n) = args("&a=1&");
+        assert_eq!(out[..n], [(&b"a"[..], &b"1"[..])]);
+    }
+
+    #[test]
+    fn args_iterator_missing_value() {
+        let (out, n) = args("a&b=&=c");
+        assert_eq!(
+            out[..n],
+            [(&b"a"[..], &b""[..]), (&b"b"[..], &b""[..]), (&b""[..], &b"c"[..])]
+        );
+    }
+
+    #[test]
+    fn decode_plus() {
+        let mut buf = [0u8; 32];
+
+        assert_eq!(spaces("", &mut buf), b"");
+        assert_eq!(spaces("abc", &mut buf), b"abc");
+        assert_eq!(spaces("a+b", &mut buf), b"a b");
+        assert_eq!(spaces("++", &mut buf), b"  ");
+        // `%2B` is left for `ngx_unescape_uri`, which decodes it as `+`.
+        assert_eq!(spaces("%2B+%20", &mut buf), b"%2B %20");
+    }
+}
@@ -0,0 +1,43 @@
+/// Define a static table of HTTP configuration directives.
+///
+/// Generates a null-terminated `[ngx_command_t; N]` array along with the FFI trampolines for the
+/// directive handlers. Each directive is described by:
+///
+/// * `context` - the configuration blocks the directive is allowed in, any of `main`, `srv`, `loc`,
+///   `ups`, `sif` (`if` in `server`), `lif` (`if` in `location`) and `lmt` (`limit_except`),
+///   separated by `|`.
+/// * `args` - the accepted number of arguments: `0` to `7`, `flag`, `any`, `one_or_more`,
+///   `two_or_more`, optionally combined with `block`, separated by `|`.
+/// * `conf` - the configuration the handler operates on: `main`, `srv` or `loc`.
+/// * `set` - the handler, a closure or a function accepting the current [`ngx_conf_t`] and a mutable
+///   reference to the module configuration, and returning `Result<(), ConfError>`.
+///   The parameter types must be specified explicitly.
+///
+/// See [`stream_commands`](crate::stream_commands) and [`mail_commands`](crate::mail_commands) for
+/// the stream and mail modules.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#config_directives>
+///
+/// # Example
+///
+/// ```rust,ignore
+/// http_commands! {
+///     static mut NGX_HTTP_FOO_COMMANDS = [
+///         "foo" => {
+///             context: srv | loc,
+///             args: 1,
+///             conf: loc,
+///             set: |cf: &mut ngx_conf_t, conf: &mut LocConf| {
+///                 conf.foo = cf.args()[1].to_string();
+///                 Ok(())
+///             },
+///         },
+///     ];
+/// }
+/// ```
+#[macro_export]
+macro_rules! http_commands {
+    ( $( $body:tt )+ ) => {
+        $crate::ngx_commands! { http; $( $body )+ }
+    };
+}
@@ -0,0 +1,92 @@
+use core::{fmt, mem};
+
+use crate::core::{conf_arg, ConfError, NgxStr, Status};
+use crate::ffi::*;
+use crate::http::Request;
+
+/// A compiled [complex value]: a string with embedded variables, e.g. `$host.example.com`.
+///
+/// The value is compiled at the configuration time and can be stored in the module configuration,
+/// then evaluated for each request.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// fn set_bucket(cf: &mut ngx_conf_t, conf: &mut LocConf) -> Result<(), ConfError> {
+///     conf.bucket = Some(ComplexValue::parse(cf, 1)?);
+///     Ok(())
+/// }
+///
+/// let bucket = conf.bucket.as_ref().unwrap().evaluate(request)?;
+/// ```
+///
+/// [complex value]: https://nginx.org/en/docs/dev/development_guide.html#http_complex_values
+#[derive(Clone)]
+pub struct ComplexValue(ngx_http_complex_value_t);
+
+impl ComplexValue {
+    /// Compiles the complex value from a string.
+    ///
+    /// The string and the compiled value are expected to be allocated from the configuration pool,
+    /// e.g. a directive argument.
+    pub fn compile(cf: &mut ngx_conf_t, value: &ngx_str_t) -> Result<Self, ConfError> {
+        let mut value = *value;
+        // SAFETY: both structures are plain C data, and all-zeroes is the expected initial state.
+        let mut cv: ngx_http_complex_value_t = unsafe { mem::zeroed() };
+        let mut ccv: ngx_http_compile_complex_value_t = unsafe { mem::zeroed() };
+
+        ccv.cf = cf;
+        ccv.value = &mut value;
+        ccv.complex_value = &mut cv;
+
+        // The errors are already logged by `ngx_http_compile_complex_value`.
+        if unsafe { ngx_http_compile_complex_value(&mut ccv) } != NGX_OK as ngx_int_t {
+            return Err(ConfError::Reported);
+        }
+
+        Ok(ComplexValue(cv))
+    }
+
+    /// Compiles the directive argument `n`, where `0` is the directive name itself.
+    ///
+    /// Mirrors `ngx_http_set_complex_value_slot`.
+    pub fn parse(cf: &mut ngx_conf_t, n: usize) -> Result<Self, ConfError> {
+        let value = conf_arg(cf, n)?;
+        Self::compile(cf, &value)
+    }
+
+    /// Returns the value if it does not contain any variables.
+    pub fn static_value(&self) -> Option<&NgxStr> {
+        if !self.0.lengths.is_null() {
+            return None;
+        }
+        Some(unsafe { NgxStr::from_ngx_str(self.0.value) })
+    }
+
+    /// Evaluates the value for the request.
+    ///
+    /// The result is allocated from the request pool, unless the value does not contain any
+    /// variables. Returns `Err(Status::NGX_ERROR)` if the evaluation fails.
+    pub fn evaluate<'r>(&self, request: &'r Request) -> Result<&'r NgxStr, Status> {
+        request.get_complex_value(&self.0).ok_or(Status::NGX_ERROR)
+    }
+
+    /// Returns the underlying [`ngx_http_complex_value_t`].
+    pub fn as_ngx_http_complex_value(&self) -> &ngx_http_complex_value_t {
+        &self.0
+    }
+}
+
+impl From<ngx_http_complex_value_t> for ComplexValue {
+    fn from(cv: ngx_http_complex_value_t) -> Self {
+        ComplexValue(cv)
+    }
+}
+
+impl fmt::Debug for ComplexValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ComplexValue")
+            .field(&format_args!("\"{}\"", self.0.value))
+            .finish()
+    }
+}
@@ -0,0 +1,461 @@
+use core::fmt::{self, Write};
+use core::{ptr, slice, str};
+
+use crate::core::NgxStr;
+use crate::ffi::*;
+use crate::http::{HeaderChain, Request};
+
+const SET_COOKIE: &str = "Set-Cookie";
+const SET_COOKIE_LOWCASE: &str = "set-cookie";
+
+impl Request {
+    /// Value of the request cookie `name`.
+    ///
+    /// The cookie name is matched case-insensitively, as with the `$cookie_name` variables.
+    pub fn cookie(&self, name: &str) -> Option<&NgxStr> {
+        let r: *const ngx_http_request_t = self.into();
+        let mut name = ngx_str_t {
+            len: name.len(),
+            data: name.as_ptr().cast_mut(),
+        };
+        let mut value = ngx_str_t {
+            len: 0,
+            data: ptr::null_mut(),
+        };
+
+        let h = unsafe {
+            ngx_http_parse_multi_header_lines(r.cast_mut(), self.get_inner().headers_in.cookie, &mut name, &mut value)
+        };
+        if h.is_null() {
+            return None;
+        }
+
+        Some(unsafe { NgxStr::from_ngx_str(value) })
+    }
+
+    /// Returns an iterator over the request cookies.
+    ///
+    /// Yields `(name, value)` pairs from all the `Cookie` headers. The values are not decoded.
+    pub fn cookies(&self) -> CookieIterator<'_> {
+        CookieIterator {
+            headers: self.cookie_headers(),
+            rest: &[],
+        }
+    }
+
+    /// Adds a `Set-Cookie` response header.
+    ///
+    /// Returns `None` if the cookie is not valid, see [`SetCookie::is_valid`], or the memory cannot
+    /// be allocated.
+    pub fn add_set_cookie(&mut self, cookie: &SetCookie) -> Option<()> {
+        if !cookie.is_valid() {
+            return None;
+        }
+
+        let mut counter = Counter(0);
+        write!(counter, "{}", cookie).ok()?;
+        let len = counter.0;
+
+        let mut pool = self.pool();
+        let data = pool.alloc_unaligned(len).cast::<u8>();
+        if data.is_null() {
+            return None;
+        }
+
+        let mut writer = SliceWriter {
+            buf: unsafe { slice::from_raw_parts_mut(data, len) },
+            pos: 0,
+        };
+        write!(writer, "{}", cookie).ok()?;
+
+        let r: *mut ngx_http_request_t = self.into();
+        // SAFETY: the response headers list is initialized with the request.
+        let h = unsafe { ngx_list_push(&mut (*r).headers_out.headers) }.cast::<ngx_table_elt_t>();
+        let h = unsafe { h.as_mut()? };
+
+        h.hash = 1;
+        h.key = ngx_str_t {
+            len: SET_COOKIE.len(),
+            data: SET_COOKIE.as_ptr().cast_mut(),
+        };
+        h.lowcase_key = SET_COOKIE_LOWCASE.as_ptr().cast_mut();
+        h.value = ngx_str_t { len, data };
+        h.next = ptr::null_mut();
+
+        Some(())
+    }
+}
+
+/// Iterator over the request cookies.
+///
+/// See [`Request::cookies`].
+pub struct CookieIterator<'a> {
+    headers: HeaderChain<'a>,
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for CookieIterator<'a> {
+    type Item = (&'a NgxStr, &'a NgxStr);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.rest.is_empty() {
+                self.rest = self.headers.next()?.value().as_bytes();
+                continue;
+            }
+
+            // Same separators as in `ngx_http_parse_multi_header_lines`.
+            let (pair, rest) = match self.rest.iter().position(|&c| c == b';' || c == b',') {
+                Some(i) => (&self.rest[..i], &self.rest[i + 1..]),
+                None => (self.rest, &[][..]),
+            };
+            self.rest = rest;
+
+            let pair = trim_spaces(pair);
+            if pair.is_empty() {
+                continue;
+            }
+
+            return Some(match pair.iter().position(|&c| c == b'=') {
+                Some(i) => (trim_spaces(&pair[..i]).into(), trim_spaces(&pair[i + 1..]).into()),
+                None => (pair.into(), (&[][..]).into()),
+            });
+        }
+    }
+}
+
+fn trim_spaces(mut bytes: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = bytes {
+        bytes = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = bytes {
+        bytes = rest;
+    }
+    bytes
+}
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    /// `SameSite=Strict`
+    Strict,
+    /// `SameSite=Lax`
+    Lax,
+    /// `SameSite=None`
+    None,
+}
+
+impl SameSite {
+    /// Returns the attribute value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// A builder for the [Set-Cookie] response header.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let cookie = SetCookie::new("session", "1234")
+///     .path("/")
+///     .max_age(3600)
+///     .same_site(SameSite::Lax)
+///     .secure(true)
+///     .http_only(true);
+/// request.add_set_cookie(&cookie).ok_or(Status::NGX_ERROR)?;
+/// ```
+///
+/// [Set-Cookie]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Set-Cookie
+#[derive(Clone, Debug)]
+pub struct SetCookie<'a> {
+    name: &'a str,
+    value: &'a str,
+    path: Option<&'a str>,
+    domain: Option<&'a str>,
+    max_age: Option<i64>,
+    same_site: Option<SameSite>,
+    secure: bool,
+    http_only: bool,
+}
+
+impl<'a> SetCookie<'a> {
+    /// Creates a cookie with the given name and value.
+    ///
+    /// The value is used as is and must be encoded by the caller if necessary.
+    /// [`Request::add_set_cookie`] rejects the cookies with the characters not allowed by
+    /// [RFC 6265], see [`SetCookie::is_valid`].
+    ///
+    /// [RFC 6265]: https://datatracker.ietf.org/doc/html/rfc6265#section-4.1.1
+    pub fn new(name: &'a str, value: &'a str) -> Self {
+        SetCookie {
+            name,
+            value,
+            path: None,
+            domain: None,
+            max_age: None,
+            same_site: None,
+            secure: false,
+            http_only: false,
+        }
+    }
+
+    /// Sets the `Path` attribute.
+    pub fn path(mut self, path: &'a str) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Sets the `Domain` attribute.
+    pub fn domain(mut self, domain: &'a str) -> Self {
+        self.domain = Some(domain);
+        self
+    }
+
+    /// Sets the `Max-Age` attribute, in seconds.
+    pub fn max_age(mut self, max_age: i64) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets the `SameSite` attribute.
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Sets the `Secure` attribute.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets the `HttpOnly` attribute.
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Checks that the cookie can be sent as is.
+    ///
+    /// The name must be a token, the value must consist of the cookie octets, optionally enclosed
+    /// in double quotes, and the `Path` and `Domain` attributes must not contain control characters
+    /// or semicolons. This rules out the header injection with CR or LF, as well as the attributes
+    /// forged with `;` in the name or value.
+    pub fn is_valid(&self) -> bool {
+        let value = self.value.as_bytes();
+        let value = match value {
+            [b'"', inner @ .., b'"'] => inner,
+            _ => value,
+        };
+
+        !self.name.is_empty()
+            && self.name.bytes().all(is_tchar)
+            && value.iter().copied().all(is_cookie_octet)
+            && self.path.unwrap_or_default().bytes().all(is_av_octet)
+            && self.domain.unwrap_or_default().bytes().all(is_av_octet)
+    }
+}
+
+/// `tchar` from RFC 9110.
+fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
+/// `cookie-octet` from RFC 6265: US-ASCII excluding CTLs, whitespace, DQUOTE, comma, semicolon,
+/// and backslash.
+fn is_cookie_octet(c: u8) -> bool {
+    matches!(c, 0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e)
+}
+
+/// The attribute value characters from RFC 6265: any CHAR except CTLs or `;`.
+fn is_av_octet(c: u8) -> bool {
+    matches!(c, 0x20..=0x7e) && c != b';'
+}
+
+impl fmt::Display for SetCookie<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(domain) = self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site.as_str())?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        Ok(())
+    }
+}
+
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let dst = self.buf.get_mut(self.pos..self.pos + s.len()).ok_or(fmt::Error)?;
+        dst.copy_from_slice(s.as_bytes());
+        self.pos += s.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem;
+
+    use super::*;
+
+    fn format<'b>(cookie: &SetCookie, buf: &'b mut [u8]) -> &'b str {
+        let mut writer = SliceWriter { buf, pos: 0 };
+        write!(writer, "{}", cookie).unwrap();
+        let SliceWriter { buf, pos } = writer;
+        str::from_utf8(&buf[..pos]).unwrap()
+    }
+
+    fn header(value: &str, next: *mut ngx_table_elt_t) -> ngx_table_elt_t {
+        // SAFETY: all-zero is a valid header.
+        let mut h: ngx_table_elt_t = unsafe { mem::zeroed() };
+        h.value = ngx_str_t {
+            len: value.len(),
+            data: value.as_ptr().cast_mut(),
+        };
+        h.next = next;
+        h
+    }
+
+    #[test]
+    fn set_cookie_format() {
+        let mut buf = [0u8; 128];
+
+        let cookie = SetCookie::new("id", "a3fWa");
+        assert_eq!(format(&cookie, &mut buf), "id=a3fWa");
+
+        let cookie = SetCookie::new("session", "1234")
+            .path("/")
+            .domain("example.com")
+            .max_age(3600)
+            .same_site(SameSite::Lax)
+            .secure(true)
+            .http_only(true);
+        assert_eq!(
+            format(&cookie, &mut buf),
+            "session=1234; Path=/; Domain=example.com; Max-Age=3600; SameSite=Lax; Secure; HttpOnly"
+        );
+
+        let cookie = SetCookie::new("id", "").max_age(-1);
+        assert_eq!(format(&cookie, &mut buf), "id=; Max-Age=-1");
+
+        let mut short = [0u8; 4];
+        let mut writer = SliceWriter {
+            buf: &mut short,
+            pos: 0,
+        };
+        assert!(write!(writer, "{}", SetCookie::new("id", "a3fWa")).is_err());
+    }
+
+    #[test]
+    fn set_cookie_validation() {
+        assert!(SetCookie::new("id", "").is_valid());
+        assert!(SetCookie::new("id", "a3fWa").is_valid());
+        assert!(SetCookie::new("id", "\"a3fWa\"").is_valid());
+        assert!(SetCookie::new("id", "a3fWa")
+            .path("/a b")
+            .domain("example.com")
+            .is_valid());
+
+        assert!(!SetCookie::new("", "a3fWa").is_valid());
+        assert!(!SetCookie::new("i d", "a3fWa").is_valid());
+        assert!(!SetCookie::new("id=x", "a3fWa").is_valid());
+        assert!(!SetCookie::new("id;", "a3fWa").is_valid());
+        assert!(!SetCookie::new("id", "a;Secure").is_valid());
+        assert!(!SetCookie::new("id", "a b").is_valid());
+        assert!(!SetCookie::new("id", "a,b").is_valid());
+        assert!(!SetCookie::new("id", "\"a").is_valid());
+        assert!(!SetCookie::new("id", "\"a\"b\"").is_valid());
+        assert!(!SetCookie::new("id", "a\r\nX-Injected: 1").is_valid());
+        assert!(!SetCookie::new("id", "a").path("/\r\n").is_valid());
+        assert!(!SetCookie::new("id", "a").path("/; HttpOnly").is_valid());
+        assert!(!SetCookie::new("id", "a").domain("example.com\n").is_valid());
+        assert!(!SetCookie::new("id", "a").domain("ex;ample.com").is_valid());
+    }
+
+    #[test]
+    fn trim() {
+        assert_eq!(trim_spaces(b""), b"");
+        assert_eq!(trim_spaces(b" \t "), b"");
+        assert_eq!(trim_spaces(b"a"), b"a");
+        assert_eq!(trim_spaces(b" \ta b\t "), b"a b");
+        assert_eq!(trim_spaces(b"a\r"), b"a\r");
+    }
+
+    #[test]
+    fn cookie_iterator() {
+        let mut second = header(" c=3,, d ;e = 5 ", ptr::null_mut());
+        let first = header("a=1; b=2;;", &mut second);
+
+        let iter = CookieIterator {
+            headers: HeaderChain::new(&first),
+            rest: &[],
+        };
+
+        let mut cookies = [(&b""[..], &b""[..]); 6];
+        let mut n = 0;
+        for (name, value) in iter {
+            cookies[n] = (name.as_bytes(), value.as_bytes());
+            n += 1;
+        }
+
+        assert_eq!(
+            cookies[..n],
+            [
+                (&b"a"[..], &b"1"[..]),
+                (b"b", b"2"),
+                (b"c", b"3"),
+                (b"d", b""),
+                (b"e", b"5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn cookie_iterator_empty() {
+        let empty = header("", ptr::null_mut());
+        let mut iter = CookieIterator {
+            headers: HeaderChain::new(&empty),
+            rest: &[],
+        };
+        assert!(iter.next().is_none());
+
+        let mut iter = CookieIterator {
+            headers: HeaderChain::new(ptr::null()),
+            rest: &[],
+        };
+        assert!(iter.next().is_none());
+    }
+}
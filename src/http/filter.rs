@@ -0,0 +1,271 @@
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::ptr;
+
+use crate::core::{FileBuffer, MemoryBuffer, Status};
+use crate::ffi::*;
+use crate::http::Request;
+
+/// Storage for the next header filter in the chain.
+///
+/// See [`HeaderFilter`].
+pub struct NextHeaderFilter(UnsafeCell<ngx_http_output_header_filter_pt>);
+
+// SAFETY: the value is only written at the configuration stage and read by the worker process
+// that owns the configuration. NGINX does not call output filters from multiple threads.
+unsafe impl Sync for NextHeaderFilter {}
+
+impl NextHeaderFilter {
+    /// Creates an empty filter storage.
+    pub const fn new() -> Self {
+        Self(UnsafeCell::new(None))
+    }
+
+    /// Passes the response headers to the next filter in the chain.
+    pub fn call(&self, request: &mut Request) -> Status {
+        match unsafe { *self.0.get() } {
+            Some(filter) => Status(unsafe { filter(request.into()) }),
+            None => Status::NGX_ERROR,
+        }
+    }
+}
+
+impl Default for NextHeaderFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Storage for the next body filter in the chain.
+///
+/// See [`BodyFilter`].
+pub struct NextBodyFilter(UnsafeCell<ngx_http_output_body_filter_pt>);
+
+// SAFETY: see `NextHeaderFilter`.
+unsafe impl Sync for NextBodyFilter {}
+
+impl NextBodyFilter {
+    /// Creates an empty filter storage.
+    pub const fn new() -> Self {
+        Self(UnsafeCell::new(None))
+    }
+
+    /// Passes the response body to the next filter in the chain.
+    ///
+    /// `None` is a valid input for the filters and usually means that the filter should flush
+    /// the buffered data.
+    pub fn call(&self, request: &mut Request, body: Option<&mut ngx_chain_t>) -> Status {
+        let body = body.map_or(ptr::null_mut(), |body| body as *mut _);
+        match unsafe { *self.0.get() } {
+            Some(filter) => Status(unsafe { filter(request.into(), body) }),
+            None => Status::NGX_ERROR,
+        }
+    }
+}
+
+impl Default for NextBodyFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The `HeaderFilter` trait implements a [response header filter].
+///
+/// The filter is inserted at the top of the filter chain with
+/// [`HeaderFilter::register_header_filter`] from the
+/// [`HTTPModule::postconfiguration`](crate::http::HTTPModule::postconfiguration) handler,
+/// and is expected to pass the request to the next filter when it's done.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// static NEXT_HEADER_FILTER: NextHeaderFilter = NextHeaderFilter::new();
+///
+/// impl HeaderFilter for Module {
+///     fn next_header_filter() -> &'static NextHeaderFilter {
+///         &NEXT_HEADER_FILTER
+///     }
+///
+///     fn filter_headers(request: &mut Request, next: &NextHeaderFilter) -> Status {
+///         if request.add_header_out("X-Filtered", "yes").is_none() {
+///             return Status::NGX_ERROR;
+///         }
+///         next.call(request)
+///     }
+/// }
+///
+/// impl HTTPModule for Module {
+///     // ...
+///     unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+///         Self::register_header_filter(&mut *cf);
+///         Status::NGX_OK.into()
+///     }
+/// }
+/// ```
+///
+/// [response header filter]: https://nginx.org/en/docs/dev/development_guide.html#http_header_filters
+pub trait HeaderFilter {
+    /// Returns the storage for the next filter in the chain.
+    ///
+    /// Each filter must use its own `static` storage.
+    fn next_header_filter() -> &'static NextHeaderFilter;
+
+    /// Processes the response headers.
+    fn filter_headers(request: &mut Request, next: &NextHeaderFilter) -> Status;
+
+    /// Inserts the filter at the top of the header filter chain.
+    ///
+    /// # Safety
+    ///
+    /// Must be called exactly once per configuration cycle, from the `postconfiguration` handler
+    /// of the module. The chain is rebuilt by NGINX for each configuration, and calling this twice
+    /// in one cycle makes the filter its own next filter, which recurses forever.
+    unsafe fn register_header_filter(_cf: &mut ngx_conf_t) {
+        *Self::next_header_filter().0.get() = ngx_http_top_header_filter;
+        ngx_http_top_header_filter = Some(Self::header_filter);
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide a valid non-null `ngx_http_request_t` argument.
+    unsafe extern "C" fn header_filter(r: *mut ngx_http_request_t) -> ngx_int_t {
+        let request = Request::from_ngx_http_request(r);
+        Self::filter_headers(request, Self::next_header_filter()).into()
+    }
+}
+
+/// The `BodyFilter` trait implements a [response body filter].
+///
+/// The filter receives the response body as a chain of buffers, which can be inspected with
+/// [`chain_buffers`], and is expected to pass the resulting chain to the next filter.
+/// The filter is inserted at the top of the filter chain with [`BodyFilter::register_body_filter`]
+/// from the [`HTTPModule::postconfiguration`](crate::http::HTTPModule::postconfiguration) handler.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// static NEXT_BODY_FILTER: NextBodyFilter = NextBodyFilter::new();
+///
+/// impl BodyFilter for Module {
+///     fn next_body_filter() -> &'static NextBodyFilter {
+///         &NEXT_BODY_FILTER
+///     }
+///
+///     fn filter_body(request: &mut Request, mut body: Option<&mut ngx_chain_t>, next: &NextBodyFilter) -> Status {
+///         if let Some(chain) = body.as_deref_mut() {
+///             for buf in chain_buffers(chain) {
+///                 match buf {
+///                     ChainBuffer::Memory(buf) => {
+///                         // inspect the buffer contents with `buf.as_bytes()`
+///                     }
+///                     ChainBuffer::File(buf) => {
+///                         // read the file region with `buf.read()`
+///                     }
+///                     ChainBuffer::Special { last_buf, .. } => {
+///                         // no data
+///                     }
+///                 }
+///             }
+///         }
+///         next.call(request, body)
+///     }
+/// }
+/// ```
+///
+/// [response body filter]: https://nginx.org/en/docs/dev/development_guide.html#http_body_filters
+pub trait BodyFilter {
+    /// Returns the storage for the next filter in the chain.
+    ///
+    /// Each filter must use its own `static` storage.
+    fn next_body_filter() -> &'static NextBodyFilter;
+
+    /// Processes a part of the response body.
+    fn filter_body(request: &mut Request, body: Option<&mut ngx_chain_t>, next: &NextBodyFilter) -> Status;
+
+    /// Inserts the filter at the top of the body filter chain.
+    ///
+    /// # Safety
+    ///
+    /// See [`HeaderFilter::register_header_filter`].
+    unsafe fn register_body_filter(_cf: &mut ngx_conf_t) {
+        *Self::next_body_filter().0.get() = ngx_http_top_body_filter;
+        ngx_http_top_body_filter = Some(Self::body_filter);
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide a valid non-null `ngx_http_request_t` argument and a valid or null
+    /// `ngx_chain_t` argument.
+    unsafe extern "C" fn body_filter(r: *mut ngx_http_request_t, chain: *mut ngx_chain_t) -> ngx_int_t {
+        let request = Request::from_ngx_http_request(r);
+        Self::filter_body(request, chain.as_mut(), Self::next_body_filter()).into()
+    }
+}
+
+/// A buffer of the response body chain.
+///
+/// See [`chain_buffers`].
+pub enum ChainBuffer {
+    /// A buffer with the contents in memory. The buffer may also refer to a file region with the
+    /// same contents.
+    Memory(MemoryBuffer),
+    /// A buffer that refers to a region of a file.
+    File(FileBuffer),
+    /// A buffer without data, used to flush or terminate the response.
+    Special {
+        /// The `flush` flag, i.e. the buffered data should be sent.
+        flush: bool,
+        /// The `last_buf` flag, i.e. the end of the response.
+        last_buf: bool,
+    },
+}
+
+impl ChainBuffer {
+    /// # Safety
+    ///
+    /// The caller has provided a valid non-null `ngx_buf_t` argument.
+    unsafe fn from_ngx_buf(buf: *mut ngx_buf_t) -> Self {
+        let b = &*buf;
+        if b.temporary() != 0 || b.memory() != 0 || b.mmap() != 0 {
+            ChainBuffer::Memory(MemoryBuffer::from_ngx_buf(buf))
+        } else if b.in_file() != 0 {
+            ChainBuffer::File(FileBuffer::from_ngx_buf(buf))
+        } else {
+            ChainBuffer::Special {
+                flush: b.flush() != 0,
+                last_buf: b.last_buf() != 0,
+            }
+        }
+    }
+}
+
+/// Iterator over the buffers in a [`ngx_chain_t`] list.
+pub struct ChainBuffers<'a> {
+    chain: *mut ngx_chain_t,
+    _marker: PhantomData<&'a mut ngx_chain_t>,
+}
+
+/// Returns an iterator over the buffers in the chain.
+pub fn chain_buffers(chain: &mut ngx_chain_t) -> ChainBuffers<'_> {
+    ChainBuffers {
+        chain,
+        _marker: PhantomData,
+    }
+}
+
+impl<'a> Iterator for ChainBuffers<'a> {
+    type Item = ChainBuffer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // SAFETY: the chain links are either null or valid for the lifetime of the head of the
+            // list.
+            let link = unsafe { self.chain.as_ref()? };
+            self.chain = link.next;
+            if !link.buf.is_null() {
+                // SAFETY: the buffer pointer is checked above.
+                return Some(unsafe { ChainBuffer::from_ngx_buf(link.buf) });
+            }
+        }
+    }
+}
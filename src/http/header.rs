@@ -0,0 +1,246 @@
+use core::marker::PhantomData;
+use core::{ptr, slice};
+
+use crate::core::NgxStr;
+use crate::ffi::*;
+use crate::http::Request;
+
+/// Wrapper struct for an [`ngx_table_elt_t`], representing a single request or response header.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#http_request>
+#[repr(transparent)]
+pub struct Header(ngx_table_elt_t);
+
+impl Header {
+    /// Create a [`Header`] from an [`ngx_table_elt_t`].
+    ///
+    /// # Safety
+    ///
+    /// The caller has provided a valid non-null pointer to a valid `ngx_table_elt_t`, whose
+    /// contents remain valid for the lifetime of the returned `Header`.
+    pub unsafe fn from_ngx_table_elt<'a>(h: *const ngx_table_elt_t) -> &'a Header {
+        &*h.cast::<Header>()
+    }
+
+    /// Header name.
+    pub fn key(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.key) }
+    }
+
+    /// Header name in lowercase.
+    ///
+    /// Returns `None` if the lowercase name was not set by the module that added the header.
+    pub fn lowcase_key(&self) -> Option<&NgxStr> {
+        if self.0.lowcase_key.is_null() {
+            return None;
+        }
+        Some(unsafe { slice::from_raw_parts(self.0.lowcase_key, self.0.key.len) }.into())
+    }
+
+    /// Hash of the lowercase header name.
+    ///
+    /// Output headers with a zero hash are ignored by NGINX.
+    pub fn hash(&self) -> ngx_uint_t {
+        self.0.hash
+    }
+
+    /// Header value.
+    pub fn value(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.value) }
+    }
+
+    /// Returns the underlying [`ngx_table_elt_t`].
+    pub fn as_ngx_table_elt(&self) -> &ngx_table_elt_t {
+        &self.0
+    }
+}
+
+/// Iterator over the headers in an [`ngx_list_t`].
+///
+/// Headers with a zero hash, i.e. removed output headers, are skipped.
+pub struct HeaderIterator<'a> {
+    part: *const ngx_list_part_t,
+    i: ngx_uint_t,
+    _marker: PhantomData<&'a ngx_list_t>,
+}
+
+impl<'a> HeaderIterator<'a> {
+    /// Creates a new iterator over the headers in the list.
+    ///
+    /// # Safety
+    ///
+    /// The caller has provided a valid [`ngx_list_t`] of [`ngx_table_elt_t`] elements.
+    pub unsafe fn new(list: &'a ngx_list_t) -> Self {
+        HeaderIterator {
+            part: &list.part,
+            i: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a> Iterator for HeaderIterator<'a> {
+    type Item = &'a Header;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let part = unsafe { self.part.as_ref()? };
+            if self.i >= part.nelts {
+                self.part = part.next;
+                self.i = 0;
+                continue;
+            }
+
+            let h = unsafe { Header::from_ngx_table_elt(part.elts.cast::<ngx_table_elt_t>().add(self.i)) };
+            self.i += 1;
+            if h.hash() != 0 {
+                return Some(h);
+            }
+        }
+    }
+}
+
+/// Iterator over a list of headers with the same name, linked with [`ngx_table_elt_t::next`].
+///
+/// NGINX links the repeated headers it parses, e.g. `Cookie` or `X-Forwarded-For`.
+pub struct HeaderChain<'a> {
+    h: *const ngx_table_elt_t,
+    _marker: PhantomData<&'a ngx_table_elt_t>,
+}
+
+impl HeaderChain<'_> {
+    pub(crate) fn new(h: *const ngx_table_elt_t) -> Self {
+        HeaderChain {
+            h,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a> Iterator for HeaderChain<'a> {
+    type Item = &'a Header;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let h = unsafe { self.h.as_ref()? };
+        self.h = h.next;
+        Some(unsafe { Header::from_ngx_table_elt(h) })
+    }
+}
+
+impl Request {
+    /// Returns an iterator over the request headers.
+    pub fn headers_in(&self) -> HeaderIterator<'_> {
+        unsafe { HeaderIterator::new(&self.get_inner().headers_in.headers) }
+    }
+
+    /// Returns an iterator over the response headers.
+    ///
+    /// Some of the response headers, e.g. `Content-Type` or `Content-Length`, are stored in the
+    /// dedicated fields of `headers_out` and may not be present in the list.
+    pub fn headers_out(&self) -> HeaderIterator<'_> {
+        unsafe { HeaderIterator::new(&self.get_inner().headers_out.headers) }
+    }
+
+    /// Client `Host` header.
+    pub fn host(&self) -> Option<&NgxStr> {
+        header_value(self.get_inner().headers_in.host)
+    }
+
+    /// Client `Content-Type` header.
+    pub fn content_type(&self) -> Option<&NgxStr> {
+        header_value(self.get_inner().headers_in.content_type)
+    }
+
+    /// Length of the request body from the `Content-Length` header.
+    ///
+    /// Returns `None` if the header is missing, e.g. for chunked requests.
+    pub fn content_length_n(&self) -> Option<off_t> {
+        let n = self.get_inner().headers_in.content_length_n;
+        (n >= 0).then_some(n)
+    }
+
+    /// Client `Authorization` header.
+    pub fn authorization(&self) -> Option<&NgxStr> {
+        header_value(self.get_inner().headers_in.authorization)
+    }
+
+    /// Returns an iterator over the client `Cookie` headers.
+    pub fn cookie_headers(&self) -> HeaderChain<'_> {
+        HeaderChain::new(self.get_inner().headers_in.cookie)
+    }
+
+    /// Returns an iterator over the client `X-Forwarded-For` headers.
+    #[cfg(ngx_feature = "http_x_forwarded_for")]
+    pub fn x_forwarded_for(&self) -> HeaderChain<'_> {
+        HeaderChain::new(self.get_inner().headers_in.x_forwarded_for)
+    }
+
+    /// Removes all response headers with the given name.
+    ///
+    /// The headers are disabled, as NGINX modules do, by setting the hash to zero. The references
+    /// from the dedicated `headers_out` fields, e.g. `location` or `content_length`, are cleared.
+    /// Returns the number of removed headers.
+    pub fn remove_header_out(&mut self, key: &str) -> usize {
+        let r: *mut ngx_http_request_t = self.into();
+        let headers_out = unsafe { &mut (*r).headers_out };
+        let mut removed = 0;
+
+        if key.eq_ignore_ascii_case("Content-Type") {
+            headers_out.content_type_len = 0;
+            headers_out.content_type.len = 0;
+            headers_out.content_type_lowcase = ptr::null_mut();
+        }
+
+        if key.eq_ignore_ascii_case("Content-Length") {
+            headers_out.content_length_n = -1;
+        }
+
+        let mut part: *mut ngx_list_part_t = &mut headers_out.headers.part;
+        while let Some(p) = unsafe { part.as_mut() } {
+            for i in 0..p.nelts {
+                let h = unsafe { &mut *p.elts.cast::<ngx_table_elt_t>().add(i) };
+                if h.hash == 0 || !h.key.as_bytes().eq_ignore_ascii_case(key.as_bytes()) {
+                    continue;
+                }
+
+                h.hash = 0;
+                removed += 1;
+
+                for field in [
+                    &mut headers_out.server,
+                    &mut headers_out.date,
+                    &mut headers_out.content_length,
+                    &mut headers_out.content_encoding,
+                    &mut headers_out.location,
+                    &mut headers_out.refresh,
+                    &mut headers_out.last_modified,
+                    &mut headers_out.content_range,
+                    &mut headers_out.accept_ranges,
+                    &mut headers_out.www_authenticate,
+                    &mut headers_out.expires,
+                    &mut headers_out.etag,
+                ] {
+                    if ptr::eq(*field, h) {
+                        *field = ptr::null_mut();
+                    }
+                }
+            }
+            part = p.next;
+        }
+
+        removed
+    }
+
+    /// Replaces all response headers with the given name with a single header.
+    ///
+    /// See [`Request::remove_header_out`].
+    pub fn set_header_out(&mut self, key: &str, value: &str) -> Option<()> {
+        self.remove_header_out(key);
+        self.add_header_out(key, value)
+    }
+}
+
+fn header_value<'a>(h: *const ngx_table_elt_t) -> Option<&'a NgxStr> {
+    let h = unsafe { h.as_ref()? };
+    Some(unsafe { NgxStr::from_ngx_str(h.value) })
+}
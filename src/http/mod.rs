@@ -1,10 +1,32 @@
+mod args;
+mod command;
+mod complex_value;
 mod conf;
+mod cookie;
+mod filter;
+mod header;
 mod module;
+mod phase;
 mod request;
+mod response;
 mod status;
+#[cfg(feature = "alloc")]
+mod suspend;
 mod upstream;
+mod variable;
 
+pub use args::*;
+pub use complex_value::*;
 pub use conf::*;
+pub use cookie::*;
+pub use filter::*;
+pub use header::*;
 pub use module::*;
+pub use phase::*;
 pub use request::*;
+pub use response::*;
 pub use status::*;
+#[cfg(feature = "alloc")]
+pub use suspend::*;
+pub use upstream::*;
+pub use variable::*;
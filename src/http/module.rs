@@ -2,15 +2,53 @@ use core::ffi::{c_char, c_void};
 use core::fmt;
 use core::ptr;
 
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::string::{String, ToString};
+#[cfg(feature = "std")]
+use std::string::{String, ToString};
+
 use crate::core::NGX_CONF_ERROR;
 use crate::core::*;
 use crate::ffi::*;
+use crate::http::{add_variables, Variable};
+use crate::ngx_conf_log_error;
 
 /// MergeConfigError - configuration cannot be merged with levels above.
+///
+/// The error is reported with [`ngx_conf_log_error`](crate::ngx_conf_log_error) at the end of the
+/// configuration block being merged, e.g. as `no value for "foo" in /etc/nginx/nginx.conf:42`.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum MergeConfigError {
     /// No value provided for configuration argument
     NoValue,
+    /// No value provided for the named configuration field
+    MissingField(&'static str),
+    /// The merged value of the named configuration field is not valid
+    #[cfg(feature = "alloc")]
+    InvalidValue {
+        /// The name of the configuration field or directive.
+        field: &'static str,
+        /// The rejected value, as shown in the error message.
+        value: String,
+        /// The reason the value was rejected.
+        reason: &'static str,
+    },
+    /// The merged configuration is not valid
+    #[cfg(feature = "alloc")]
+    Message(String),
+}
+
+#[cfg(feature = "alloc")]
+impl MergeConfigError {
+    /// Creates a [`MergeConfigError::InvalidValue`] error, formatting the rejected `value`.
+    pub fn invalid_value(field: &'static str, value: impl fmt::Display, reason: &'static str) -> Self {
+        MergeConfigError::InvalidValue {
+            field,
+            value: value.to_string(),
+            reason,
+        }
+    }
 }
 
 #[cfg(feature = "std")]
@@ -20,6 +58,13 @@ impl fmt::Display for MergeConfigError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
             MergeConfigError::NoValue => "no value".fmt(fmt),
+            MergeConfigError::MissingField(field) => write!(fmt, "no value for \"{field}\""),
+            #[cfg(feature = "alloc")]
+            MergeConfigError::InvalidValue { field, value, reason } => {
+                write!(fmt, "invalid value \"{value}\" for \"{field}\": {reason}")
+            }
+            #[cfg(feature = "alloc")]
+            MergeConfigError::Message(msg) => msg.fmt(fmt),
         }
     }
 }
@@ -28,6 +73,9 @@ impl fmt::Display for MergeConfigError {
 ///
 /// A module configuration should implement this trait for setting its configuration throughout
 /// each level.
+///
+/// With the `derive` feature enabled, the trait can be implemented with `#[derive(Merge)]`.
+/// See [`Unset`] for the details.
 pub trait Merge {
     /// Module merge function.
     ///
@@ -42,6 +90,111 @@ impl Merge for () {
     }
 }
 
+#[cfg(feature = "derive")]
+pub use ngx_derive::Merge;
+
+/// The `Unset` trait describes configuration values that can be left unset at a configuration
+/// level, similar to the `NGX_CONF_UNSET` family of constants.
+///
+/// The unset values are replaced with the values from the enclosing configuration level by
+/// `#[derive(Merge)]`.
+pub trait Unset {
+    /// The type of a value that has been set.
+    type Value;
+
+    /// The unset value.
+    const UNSET: Self;
+
+    /// Returns `true` if the value is unset.
+    fn is_unset(&self) -> bool;
+
+    /// Creates a set value.
+    fn from_value(value: Self::Value) -> Self;
+}
+
+impl<T> Unset for Option<T> {
+    type Value = T;
+
+    const UNSET: Self = None;
+
+    fn is_unset(&self) -> bool {
+        self.is_none()
+    }
+
+    fn from_value(value: T) -> Self {
+        Some(value)
+    }
+}
+
+impl<T> Unset for *const T {
+    type Value = Self;
+
+    const UNSET: Self = usize::MAX as *const T;
+
+    fn is_unset(&self) -> bool {
+        *self == Self::UNSET
+    }
+
+    fn from_value(value: Self) -> Self {
+        value
+    }
+}
+
+impl<T> Unset for *mut T {
+    type Value = Self;
+
+    const UNSET: Self = usize::MAX as *mut T;
+
+    fn is_unset(&self) -> bool {
+        *self == Self::UNSET
+    }
+
+    fn from_value(value: Self) -> Self {
+        value
+    }
+}
+
+macro_rules! impl_unset {
+    ($unset:expr => $( $ty:ty ),+) => {
+        $(
+            impl Unset for $ty {
+                type Value = Self;
+
+                const UNSET: Self = $unset;
+
+                fn is_unset(&self) -> bool {
+                    *self == Self::UNSET
+                }
+
+                fn from_value(value: Self) -> Self {
+                    value
+                }
+            }
+        )+
+    };
+}
+
+// NGX_CONF_UNSET
+impl_unset!(-1 => i8, i16, i32, i64, isize);
+// NGX_CONF_UNSET_UINT, NGX_CONF_UNSET_SIZE, NGX_CONF_UNSET_MSEC
+impl_unset!(Self::MAX => u8, u16, u32, u64, usize);
+
+/// Reports a merge error against the configuration being processed and converts the result to the
+/// value expected from the `merge_*_conf` handlers.
+///
+/// # Safety
+///
+/// The caller has provided a valid non-null `ngx_conf_t` pointer.
+pub(crate) unsafe fn merge_result(cf: *mut ngx_conf_t, result: Result<(), MergeConfigError>) -> *mut c_char {
+    match result {
+        Ok(_) => ptr::null_mut(),
+        Err(err) => {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "{}", err);
+            NGX_CONF_ERROR as _
+        }
+    }
+}
+
 /// The `HTTPModule` trait provides the NGINX configuration stage interface.
 ///
 /// These functions allocate structures, initialize them, and merge through the configuration
@@ -56,12 +209,20 @@ pub trait HTTPModule {
     /// Configuration in a `location` block within the `http` block.
     type LocConf: Merge + Default;
 
+    /// Variables provided by the module.
+    ///
+    /// The variables are registered by the default [`HTTPModule::preconfiguration`] handler.
+    const VARIABLES: &'static [Variable] = &[];
+
     /// # Safety
     ///
     /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
     /// guard against null inputs or risk runtime errors.
-    unsafe extern "C" fn preconfiguration(_cf: *mut ngx_conf_t) -> ngx_int_t {
-        Status::NGX_OK.into()
+    unsafe extern "C" fn preconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+        match add_variables(&mut *cf, Self::VARIABLES) {
+            Ok(()) => Status::NGX_OK.into(),
+            Err(status) => status.into(),
+        }
     }
 
     /// # Safety
@@ -102,13 +263,10 @@ pub trait HTTPModule {
     ///
     /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
     /// guard against null inputs or risk runtime errors.
-    unsafe extern "C" fn merge_srv_conf(_cf: *mut ngx_conf_t, prev: *mut c_void, conf: *mut c_void) -> *mut c_char {
+    unsafe extern "C" fn merge_srv_conf(cf: *mut ngx_conf_t, prev: *mut c_void, conf: *mut c_void) -> *mut c_char {
         let prev = &mut *(prev as *mut Self::SrvConf);
         let conf = &mut *(conf as *mut Self::SrvConf);
-        match conf.merge(prev) {
-            Ok(_) => ptr::null_mut(),
-            Err(_) => NGX_CONF_ERROR as _,
-        }
+        merge_result(cf, conf.merge(prev))
     }
 
     /// # Safety
@@ -124,12 +282,9 @@ pub trait HTTPModule {
     ///
     /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
     /// guard against null inputs or risk runtime errors.
-    unsafe extern "C" fn merge_loc_conf(_cf: *mut ngx_conf_t, prev: *mut c_void, conf: *mut c_void) -> *mut c_char {
+    unsafe extern "C" fn merge_loc_conf(cf: *mut ngx_conf_t, prev: *mut c_void, conf: *mut c_void) -> *mut c_char {
         let prev = &mut *(prev as *mut Self::LocConf);
         let conf = &mut *(conf as *mut Self::LocConf);
-        match conf.merge(prev) {
-            Ok(_) => ptr::null_mut(),
-            Err(_) => NGX_CONF_ERROR as _,
-        }
+        merge_result(cf, conf.merge(prev))
     }
 }
@@ -0,0 +1,124 @@
+use core::ptr::addr_of;
+
+use crate::core::Status;
+use crate::ffi::*;
+use crate::http::ngx_http_conf_get_module_main_conf;
+
+/// HTTP request processing phases.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#http_phases>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// The first phase, `NGX_HTTP_POST_READ_PHASE`. The realip module handlers run here.
+    PostRead = ngx_http_phases_NGX_HTTP_POST_READ_PHASE as isize,
+    /// `NGX_HTTP_SERVER_REWRITE_PHASE`, the rewrite directives defined in a `server` block.
+    ServerRewrite = ngx_http_phases_NGX_HTTP_SERVER_REWRITE_PHASE as isize,
+    /// `NGX_HTTP_FIND_CONFIG_PHASE`, the location lookup. Custom handlers are not invoked.
+    FindConfig = ngx_http_phases_NGX_HTTP_FIND_CONFIG_PHASE as isize,
+    /// `NGX_HTTP_REWRITE_PHASE`, the rewrite directives defined in a `location` block.
+    Rewrite = ngx_http_phases_NGX_HTTP_REWRITE_PHASE as isize,
+    /// `NGX_HTTP_POST_REWRITE_PHASE`, the location change after rewrite. Custom handlers are not
+    /// invoked.
+    PostRewrite = ngx_http_phases_NGX_HTTP_POST_REWRITE_PHASE as isize,
+    /// `NGX_HTTP_PREACCESS_PHASE`, the request limits, e.g. `limit_req`.
+    PreAccess = ngx_http_phases_NGX_HTTP_PREACCESS_PHASE as isize,
+    /// `NGX_HTTP_ACCESS_PHASE`, the access checks, e.g. `allow` and `auth_basic`.
+    Access = ngx_http_phases_NGX_HTTP_ACCESS_PHASE as isize,
+    /// `NGX_HTTP_POST_ACCESS_PHASE`, the `satisfy any` processing. Custom handlers are not invoked.
+    PostAccess = ngx_http_phases_NGX_HTTP_POST_ACCESS_PHASE as isize,
+    /// `NGX_HTTP_PRECONTENT_PHASE`, the actions before generating content, e.g. `try_files`.
+    PreContent = ngx_http_phases_NGX_HTTP_PRECONTENT_PHASE as isize,
+    /// `NGX_HTTP_CONTENT_PHASE`, the response generation.
+    Content = ngx_http_phases_NGX_HTTP_CONTENT_PHASE as isize,
+    /// `NGX_HTTP_LOG_PHASE`, the request logging.
+    Log = ngx_http_phases_NGX_HTTP_LOG_PHASE as isize,
+}
+
+impl Phase {
+    /// Returns `true` if NGINX invokes the handlers registered for the phase.
+    const fn has_handlers(self) -> bool {
+        !matches!(self, Phase::FindConfig | Phase::PostRewrite | Phase::PostAccess)
+    }
+}
+
+impl From<Phase> for ngx_http_phases {
+    fn from(value: Phase) -> Self {
+        value as ngx_http_phases
+    }
+}
+
+/// Registers a request handler for the HTTP processing phase.
+///
+/// The handlers are stored in the HTTP core module configuration and can only be added from the
+/// [`HTTPModule::postconfiguration`](crate::http::HTTPModule::postconfiguration) handler.
+/// Returns `Err(Status::NGX_ERROR)` for [`Phase::FindConfig`], [`Phase::PostRewrite`] and
+/// [`Phase::PostAccess`], as NGINX never invokes custom handlers in these phases, and if the phase
+/// handlers are not available or the memory allocation fails.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+///     match http::register_phase_handler(&mut *cf, Phase::Access, access_handler) {
+///         Ok(()) => Status::NGX_OK.into(),
+///         Err(status) => status.into(),
+///     }
+/// }
+/// ```
+pub fn register_phase_handler(
+    cf: &mut ngx_conf_t,
+    phase: Phase,
+    handler: unsafe extern "C" fn(*mut ngx_http_request_t) -> ngx_int_t,
+) -> Result<(), Status> {
+    if !phase.has_handlers() || cf.module_type != NGX_HTTP_MODULE as ngx_uint_t || cf.ctx.is_null() {
+        return Err(Status::NGX_ERROR);
+    }
+
+    // SAFETY: `cf` is the configuration of an HTTP module, so `ctx` points to `ngx_http_conf_ctx_t`
+    // with the HTTP core module configuration.
+    let cmcf = unsafe { ngx_http_conf_get_module_main_conf(cf, &*addr_of!(ngx_http_core_module)) };
+    let Some(cmcf) = (unsafe { cmcf.as_mut() }) else {
+        return Err(Status::NGX_ERROR);
+    };
+
+    let handlers = &mut cmcf.phases[ngx_http_phases::from(phase) as usize].handlers;
+    // The arrays are initialized right before the postconfiguration handlers are called.
+    if handlers.elts.is_null() {
+        return Err(Status::NGX_ERROR);
+    }
+
+    let h = unsafe { ngx_array_push(handlers) } as *mut ngx_http_handler_pt;
+    if h.is_null() {
+        return Err(Status::NGX_ERROR);
+    }
+
+    unsafe { *h = Some(handler) };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_values() {
+        assert_eq!(
+            ngx_http_phases::from(Phase::PostRead),
+            ngx_http_phases_NGX_HTTP_POST_READ_PHASE
+        );
+        assert_eq!(
+            ngx_http_phases::from(Phase::Content),
+            ngx_http_phases_NGX_HTTP_CONTENT_PHASE
+        );
+        assert_eq!(ngx_http_phases::from(Phase::Log), ngx_http_phases_NGX_HTTP_LOG_PHASE);
+    }
+
+    #[test]
+    fn phase_handlers() {
+        assert!(Phase::Access.has_handlers());
+        assert!(Phase::Content.has_handlers());
+        assert!(!Phase::FindConfig.has_handlers());
+        assert!(!Phase::PostRewrite.has_handlers());
+        assert!(!Phase::PostAccess.has_handlers());
+    }
+}
@@ -1,11 +1,18 @@
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::boxed::Box;
 use core::ffi::c_void;
 use core::fmt;
 use core::slice;
 use core::str::FromStr;
+#[cfg(feature = "alloc")]
+use core::{mem, ptr};
+#[cfg(feature = "std")]
+use std::boxed::Box;
 
 use crate::core::*;
 use crate::ffi::*;
 use crate::http::status::*;
+use crate::http::{chain_buffers, ChainBuffer, Upstream, UpstreamState};
 use crate::ngx_null_string;
 
 /// Define a static request handler.
@@ -22,6 +29,30 @@ macro_rules! http_request_handler {
     };
 }
 
+/// Define a static request body handler.
+///
+/// The handler is invoked once the request body is read with [`Request::read_client_request_body`]
+/// and is expected to take a single [`Request`] argument and return a [`Status`]. The request is
+/// finalized with the returned status, which also releases the reference to the request taken by
+/// `read_client_request_body`.
+///
+/// Finalizing the request with [`Status::NGX_DONE`] releases the reference as well. Handlers that
+/// complete the request asynchronously must take another reference before returning `NGX_DONE`,
+/// i.e. increment `r->main->count`, and finalize the request later, once the work is done. This is
+/// what [`Request::suspend`] does, with the request finalized by [`SuspendedRequest::finalize`].
+///
+/// [`SuspendedRequest::finalize`]: crate::http::SuspendedRequest::finalize
+#[macro_export]
+macro_rules! http_request_body_handler {
+    ( $name: ident, $handler: expr ) => {
+        unsafe extern "C" fn $name(r: *mut $crate::ffi::ngx_http_request_t) {
+            let status: $crate::core::Status =
+                $handler(unsafe { &mut $crate::http::Request::from_ngx_http_request(r) });
+            unsafe { $crate::ffi::ngx_http_finalize_request(r, status.0) };
+        }
+    };
+}
+
 /// Define a static post subrequest handler.
 ///
 /// Handlers are expected to take a single [`Request`] argument and return a [`Status`].
@@ -43,6 +74,8 @@ macro_rules! http_subrequest_handler {
 /// The set handler allows setting the property referenced by the variable.
 /// The set handler expects a [`Request`], [`mut ngx_variable_value_t`], and a [`usize`].
 /// Variables: <https://nginx.org/en/docs/dev/development_guide.html#http_variables>
+///
+/// See [`Variable`](crate::http::Variable) for a typed alternative.
 #[macro_export]
 macro_rules! http_variable_set {
     ( $name: ident, $handler: expr ) => {
@@ -66,6 +99,8 @@ macro_rules! http_variable_set {
 /// Variable evaluators accept a [`Request`] input argument and two output
 /// arguments: [`ngx_variable_value_t`] and [`usize`].
 /// Variables: <https://nginx.org/en/docs/dev/development_guide.html#http_variables>
+///
+/// See [`Variable`](crate::http::Variable) for a typed alternative.
 #[macro_export]
 macro_rules! http_variable_get {
     ( $name: ident, $handler: expr ) => {
@@ -125,20 +160,45 @@ impl Request {
         unsafe { Pool::from_ngx_pool(self.0.pool) }
     }
 
-    /// Returns the result as an `Option` if it exists, otherwise `None`.
-    ///
-    /// The option wraps an ngx_http_upstream_t instance, it will be none when the underlying NGINX request
-    /// does not have a pointer to a [`ngx_http_upstream_t`] upstream structure.
+    /// The request to the upstream server, if any.
     ///
     /// [`ngx_http_upstream_t`] is best described in
     /// <https://nginx.org/en/docs/dev/development_guide.html#http_load_balancing>
-    pub fn upstream(&self) -> Option<*mut ngx_http_upstream_t> {
+    pub fn upstream(&self) -> Option<&Upstream> {
+        // SAFETY: the upstream is either NULL or allocated from the request pool.
+        unsafe { self.0.upstream.cast::<Upstream>().as_ref() }
+    }
+
+    /// Raw pointer to the request to the upstream server, if any.
+    ///
+    /// See [`Request::upstream`] for a safe accessor.
+    pub fn upstream_raw(&self) -> Option<*mut ngx_http_upstream_t> {
         if self.0.upstream.is_null() {
             return None;
         }
         Some(self.0.upstream)
     }
 
+    /// Mutable reference to the request to the upstream server, if any.
+    pub fn upstream_mut(&mut self) -> Option<&mut Upstream> {
+        // SAFETY: the upstream is either NULL or allocated from the request pool.
+        unsafe { self.0.upstream.cast::<Upstream>().as_mut() }
+    }
+
+    /// States of the attempts to connect to the upstream servers.
+    ///
+    /// The values are used for the `$upstream_*` variables, e.g. `$upstream_response_time`.
+    pub fn upstream_states(&self) -> &[UpstreamState] {
+        // SAFETY: the array is either NULL or allocated from the request pool, and contains
+        // `ngx_http_upstream_state_t` elements.
+        unsafe {
+            match self.0.upstream_states.as_ref() {
+                Some(states) if states.nelts != 0 => slice::from_raw_parts(states.elts.cast(), states.nelts),
+                _ => &[],
+            }
+        }
+    }
+
     /// Pointer to a [`ngx_connection_t`] client connection object.
     ///
     /// [`ngx_connection_t`]: https://nginx.org/en/docs/dev/development_guide.html#connection
@@ -244,6 +304,98 @@ impl Request {
         unsafe { Status(ngx_http_discard_request_body(&mut self.0)) }
     }
 
+    /// Start reading the [request body].
+    ///
+    /// The `post_handler` defined with [`http_request_body_handler`](crate::http_request_body_handler)
+    /// is called once the whole body is read, either immediately or from a later event. The
+    /// request reference count is incremented until the handler finalizes the request.
+    ///
+    /// Returns the status to be returned from the content handler: `NGX_DONE` when the body
+    /// reading is started, or a special response code on failure.
+    ///
+    /// [request body]: https://nginx.org/en/docs/dev/development_guide.html#http_request_body
+    pub fn read_client_request_body(&mut self, post_handler: unsafe extern "C" fn(*mut ngx_http_request_t)) -> Status {
+        let rc = unsafe { ngx_http_read_client_request_body(&mut self.0, Some(post_handler)) };
+        if rc >= NGX_HTTP_SPECIAL_RESPONSE as ngx_int_t {
+            return Status(rc);
+        }
+        Status::NGX_DONE
+    }
+
+    /// Start reading the [request body] with a closure as the completion handler.
+    ///
+    /// Same as [`Request::read_client_request_body`], but the handler is a Rust closure stored in
+    /// the request pool. The request is finalized with the status returned from the closure, as
+    /// with the handlers defined with [`http_request_body_handler`](crate::http_request_body_handler).
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// http_request_handler!(content_handler, |request: &mut Request| {
+    ///     request.read_client_request_body_with(|request| {
+    ///         for buf in request.request_body().into_iter().flatten() {
+    ///             // ... process the body
+    ///         }
+    ///         // ... send the response
+    ///         Status::NGX_OK
+    ///     })
+    /// });
+    /// ```
+    ///
+    /// [request body]: https://nginx.org/en/docs/dev/development_guide.html#http_request_body
+    #[cfg(feature = "alloc")]
+    pub fn read_client_request_body_with<F>(&mut self, handler: F) -> Status
+    where
+        F: FnOnce(&mut Request) -> Status + 'static,
+    {
+        let r: *mut ngx_http_request_t = &mut self.0;
+
+        // The handler is found by the request, as the subrequests share the pool of the main request.
+        let data = self.pool().alloc(mem::size_of::<BodyHandler>()).cast::<BodyHandler>();
+        if data.is_null() {
+            return HTTPStatus::INTERNAL_SERVER_ERROR.into();
+        }
+
+        unsafe {
+            let cln = ngx_pool_cleanup_add(self.0.pool, 0);
+            if cln.is_null() {
+                return HTTPStatus::INTERNAL_SERVER_ERROR.into();
+            }
+
+            ptr::write(
+                data,
+                BodyHandler {
+                    request: r,
+                    handler: Some(Box::new(handler)),
+                },
+            );
+            (*cln).handler = Some(body_handler_cleanup);
+            (*cln).data = data.cast();
+        }
+
+        self.read_client_request_body(body_handler)
+    }
+
+    /// Returns an iterator over the buffers of the [request body] read with
+    /// [`Request::read_client_request_body`].
+    ///
+    /// The body is `None` if it has not been read, or was discarded. Depending on the
+    /// configuration, parts of the body may be stored in a temporary file. The buffers without data
+    /// are skipped, so the iterator only yields [`ChainBuffer::Memory`] and [`ChainBuffer::File`].
+    ///
+    /// [request body]: https://nginx.org/en/docs/dev/development_guide.html#http_request_body
+    pub fn request_body(&mut self) -> Option<impl Iterator<Item = ChainBuffer> + '_> {
+        let body = unsafe { self.0.request_body.as_mut()? };
+        // SAFETY: the chain is owned by the request and is either null or a valid list.
+        let bufs = unsafe { body.bufs.as_mut() };
+        Some(
+            bufs.map(chain_buffers)
+                .into_iter()
+                .flatten()
+                .filter(|buf| !matches!(buf, ChainBuffer::Special { .. })),
+        )
+    }
+
     /// Client HTTP [User-Agent].
     ///
     /// [User-Agent]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/User-Agent
@@ -394,12 +546,16 @@ impl Request {
 
     /// Iterate over headers_in
     /// each header item is (&str, &str) (borrowed)
+    ///
+    /// See [`Request::headers_in`] for an iterator over [`Header`](crate::http::Header) values.
     pub fn headers_in_iterator(&self) -> NgxListIterator {
         unsafe { list_iterator(&self.0.headers_in.headers) }
     }
 
     /// Iterate over headers_out
     /// each header item is (&str, &str) (borrowed)
+    ///
+    /// See [`Request::headers_out`] for an iterator over [`Header`](crate::http::Header) values.
     pub fn headers_out_iterator(&self) -> NgxListIterator {
         unsafe { list_iterator(&self.0.headers_out.headers) }
     }
@@ -420,6 +576,47 @@ impl fmt::Debug for Request {
     }
 }
 
+#[cfg(feature = "alloc")]
+type BodyHandlerFn = Box<dyn FnOnce(&mut Request) -> Status>;
+
+/// The closure passed to [`Request::read_client_request_body_with`], stored in the request pool.
+#[cfg(feature = "alloc")]
+struct BodyHandler {
+    request: *mut ngx_http_request_t,
+    handler: Option<BodyHandlerFn>,
+}
+
+#[cfg(feature = "alloc")]
+unsafe extern "C" fn body_handler_cleanup(data: *mut c_void) {
+    ptr::drop_in_place(data.cast::<BodyHandler>());
+}
+
+/// The `post_handler` of [`Request::read_client_request_body_with`].
+#[cfg(feature = "alloc")]
+unsafe extern "C" fn body_handler(r: *mut ngx_http_request_t) {
+    let mut cln = (*(*r).pool).cleanup;
+    let mut handler = None;
+
+    while let Some(c) = cln.as_ref() {
+        if c.handler
+            .is_some_and(|f| f as *const () == body_handler_cleanup as *const ())
+        {
+            let data = &mut *c.data.cast::<BodyHandler>();
+            if data.request == r && data.handler.is_some() {
+                handler = data.handler.take();
+                break;
+            }
+        }
+        cln = c.next;
+    }
+
+    let status = match handler {
+        Some(handler) => handler(Request::from_ngx_http_request(r)),
+        None => Status::NGX_ERROR,
+    };
+    ngx_http_finalize_request(r, status.0);
+}
+
 /// Iterator for [`ngx_list_t`] types.
 ///
 /// Implementes the core::iter::Iterator trait.
@@ -456,10 +653,6 @@ pub unsafe fn list_iterator(list: &ngx_list_t) -> NgxListIterator {
 
 // iterator for ngx_list_t
 impl<'a> Iterator for NgxListIterator<'a> {
-    // TODO: try to use struct instead of &str pair
-    // something like pub struct Header(ngx_table_elt_t);
-    // then header would have key and value
-
     type Item = (&'a str, &'a str);
 
     fn next(&mut self) -> Option<Self::Item> {
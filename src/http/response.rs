@@ -0,0 +1,254 @@
+use core::ptr;
+
+use crate::core::{Buffer, Status};
+use crate::ffi::*;
+use crate::http::{HTTPStatus, Request};
+
+/// The body of a [`Response`].
+pub enum Body<'a> {
+    /// No response body.
+    Empty,
+    /// The body is copied to a buffer allocated from the request pool.
+    Bytes(&'a [u8]),
+    /// The body is sent without copying.
+    Static(&'static str),
+    /// The body is a prepared chain of buffers.
+    ///
+    /// The `last_buf` flag is set on the last buffer of the chain.
+    Chain(&'a mut ngx_chain_t),
+}
+
+impl<'a> From<&'a [u8]> for Body<'a> {
+    fn from(value: &'a [u8]) -> Self {
+        Body::Bytes(value)
+    }
+}
+
+impl<'a> From<&'a str> for Body<'a> {
+    fn from(value: &'a str) -> Self {
+        Body::Bytes(value.as_bytes())
+    }
+}
+
+impl<'a> From<&'a mut ngx_chain_t> for Body<'a> {
+    fn from(value: &'a mut ngx_chain_t) -> Self {
+        Body::Chain(value)
+    }
+}
+
+/// A builder for a complete response to a request.
+///
+/// The builder sets the response status, headers and `Content-Length`, sends the headers and, unless
+/// the response does not need a body, e.g. for a `HEAD` request, sends the body.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// http_request_handler!(hello_handler, |request: &mut Request| {
+///     request
+///         .response(HTTPStatus::OK)
+///         .content_type("text/plain")
+///         .header("X-Hello", "world")
+///         .body("Hello, world!\n")
+///         .send()
+/// });
+/// ```
+pub struct Response<'a> {
+    request: &'a mut Request,
+    status: HTTPStatus,
+    body: Body<'a>,
+    failed: bool,
+}
+
+impl Request {
+    /// Creates a [`Response`] builder for the request.
+    pub fn response(&mut self, status: HTTPStatus) -> Response<'_> {
+        Response {
+            request: self,
+            status,
+            body: Body::Empty,
+            failed: false,
+        }
+    }
+}
+
+impl<'a> Response<'a> {
+    /// Adds a response header.
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        if self.request.add_header_out(key, value).is_none() {
+            self.failed = true;
+        }
+        self
+    }
+
+    /// Sets the `Content-Type` response header.
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        let r: *mut ngx_http_request_t = (&mut *self.request).into();
+        let pool = self.request.get_inner().pool;
+
+        match unsafe { ngx_str_t::from_bytes(pool, content_type.as_bytes()) } {
+            Some(value) => unsafe {
+                (*r).headers_out.content_type = value;
+                (*r).headers_out.content_type_len = value.len;
+                (*r).headers_out.content_type_lowcase = ptr::null_mut();
+            },
+            None => self.failed = true,
+        }
+        self
+    }
+
+    /// Sets the response body.
+    pub fn body(mut self, body: impl Into<Body<'a>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Sends the response.
+    ///
+    /// The body and `Content-Length` are omitted for the `1xx`, `204 No Content` and
+    /// `304 Not Modified` responses, which must not have a body. Returns the status to be returned
+    /// from the content handler.
+    pub fn send(self) -> Status {
+        let Response {
+            request,
+            status,
+            body,
+            failed,
+        } = self;
+
+        if failed {
+            return Status::NGX_ERROR;
+        }
+
+        let r: *mut ngx_http_request_t = (&mut *request).into();
+
+        if !has_body(&status) {
+            request.set_status(status);
+            // SAFETY: `r` is the request being responded to.
+            unsafe {
+                (*r).headers_out.content_length_n = -1;
+                (*r).set_header_only(1);
+            }
+            return request.send_header();
+        }
+
+        let mut pool = request.pool();
+
+        let chain = match body {
+            Body::Empty => None,
+            Body::Bytes([]) => None,
+            Body::Bytes(bytes) => {
+                let Some(mut buf) = pool.create_buffer(bytes.len()) else {
+                    return Status::NGX_ERROR;
+                };
+                unsafe {
+                    let buf = buf.as_ngx_buf_mut();
+                    ptr::copy_nonoverlapping(bytes.as_ptr(), (*buf).last, bytes.len());
+                    (*buf).last = (*buf).last.add(bytes.len());
+                }
+                Some(ChainBody::Owned(buf.as_ngx_buf_mut()))
+            }
+            Body::Static("") => None,
+            Body::Static(str) => {
+                let Some(mut buf) = pool.create_buffer_from_static_str(str) else {
+                    return Status::NGX_ERROR;
+                };
+                Some(ChainBody::Owned(buf.as_ngx_buf_mut()))
+            }
+            Body::Chain(chain) => Some(ChainBody::Borrowed(chain)),
+        };
+
+        request.set_status(status);
+        request.set_content_length_n(match chain {
+            Some(ref chain) => chain.len(),
+            None => 0,
+        });
+
+        let rc = request.send_header();
+        if rc == Status::NGX_ERROR || rc.0 > Status::NGX_OK.0 || request.header_only() {
+            return rc;
+        }
+
+        let is_main = request.is_main();
+        match chain {
+            Some(ChainBody::Owned(buf)) => {
+                let mut cl = ngx_chain_t {
+                    buf,
+                    next: ptr::null_mut(),
+                };
+                set_last(&mut cl, is_main);
+                request.output_filter(&mut cl)
+            }
+            Some(ChainBody::Borrowed(cl)) => {
+                set_last(cl, is_main);
+                request.output_filter(cl)
+            }
+            None => Status(unsafe { ngx_http_send_special(r, NGX_HTTP_LAST as ngx_uint_t) }),
+        }
+    }
+}
+
+enum ChainBody<'a> {
+    Owned(*mut ngx_buf_t),
+    Borrowed(&'a mut ngx_chain_t),
+}
+
+impl ChainBody<'_> {
+    fn len(&self) -> usize {
+        match self {
+            ChainBody::Owned(buf) => unsafe { buf_size(&**buf) },
+            ChainBody::Borrowed(chain) => {
+                let mut len = 0;
+                let mut cl: *const ngx_chain_t = &**chain;
+                while let Some(link) = unsafe { cl.as_ref() } {
+                    len += unsafe { buf_size(&*link.buf) };
+                    cl = link.next;
+                }
+                len
+            }
+        }
+    }
+}
+
+/// Returns `false` for the response status codes that do not allow a body, see RFC 9110.
+fn has_body(status: &HTTPStatus) -> bool {
+    !matches!(status.0, 100..=199 | 204 | 304)
+}
+
+/// Returns the size of the buffer contents, as `ngx_buf_size`.
+fn buf_size(buf: &ngx_buf_t) -> usize {
+    if buf.temporary() != 0 || buf.memory() != 0 || buf.mmap() != 0 {
+        buf.last as usize - buf.pos as usize
+    } else {
+        (buf.file_last - buf.file_pos) as usize
+    }
+}
+
+/// Marks the last buffer in the chain.
+fn set_last(chain: &mut ngx_chain_t, is_main: bool) {
+    let mut cl: *mut ngx_chain_t = chain;
+    unsafe {
+        while !(*cl).next.is_null() {
+            cl = (*cl).next;
+        }
+        let buf = &mut *(*cl).buf;
+        buf.set_last_buf(if is_main { 1 } else { 0 });
+        buf.set_last_in_chain(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_has_body() {
+        assert!(has_body(&HTTPStatus::OK));
+        assert!(has_body(&HTTPStatus::NOT_FOUND));
+        assert!(has_body(&HTTPStatus(205)));
+        assert!(!has_body(&HTTPStatus::CONTINUE));
+        assert!(!has_body(&HTTPStatus(103)));
+        assert!(!has_body(&HTTPStatus::NO_CONTENT));
+        assert!(!has_body(&HTTPStatus::NOT_MODIFIED));
+    }
+}
@@ -0,0 +1,297 @@
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::rc::Rc;
+use core::cell::Cell;
+use core::ptr::{addr_of, NonNull};
+#[cfg(feature = "std")]
+use std::rc::Rc;
+#[cfg(feature = "async")]
+use std::thread::{self, ThreadId};
+
+use crate::core::Status;
+use crate::ffi::*;
+use crate::http::{HTTPStatus, Request};
+
+#[cfg(feature = "async")]
+use crate::async_::Notifier;
+
+impl Request {
+    /// Suspends the request processing until the returned handle is resumed or finalized.
+    ///
+    /// The handle owns a reference to the request, i.e. the request is not freed until the handle
+    /// is released, even if the client closes the connection. The handler that suspended the
+    /// request must return `NGX_DONE`.
+    ///
+    /// The request can still be terminated by NGINX, e.g. with `ngx_http_terminate_request` on a
+    /// fatal error in a subrequest, regardless of the references held. The handle is invalidated
+    /// in this case, and resuming or finalizing it does nothing.
+    ///
+    /// Returns `Err(Status::NGX_ERROR)` if the memory cannot be allocated.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// http_request_handler!(access_handler, |request: &mut Request| {
+    ///     if let Some(ctx) = request.get_module_ctx::<RequestCtx>(module) {
+    ///         // Invoked again after `resume()`.
+    ///         return ctx.status;
+    ///     }
+    ///     // ...
+    ///     let suspended = match request.suspend() {
+    ///         Ok(suspended) => suspended,
+    ///         Err(status) => return status,
+    ///     };
+    ///     spawn(async move {
+    ///         let status = check().await;
+    ///         // ... store the status in the request context
+    ///         suspended.resume();
+    ///     })
+    ///     .detach();
+    ///
+    ///     Status::NGX_DONE
+    /// });
+    /// ```
+    pub fn suspend(&mut self) -> Result<SuspendedRequest, Status> {
+        let freed = Rc::new(Cell::new(false));
+        if self.pool().allocate(RequestGuard(freed.clone())).is_null() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        let r: *mut ngx_http_request_t = self.into();
+
+        // The content handlers and the content phase handlers are finalized by NGINX with the
+        // returned status. `NGX_DONE` releases one reference to the request.
+        let content = self.in_content_phase();
+
+        unsafe {
+            let main = &mut *(*r).main;
+            main.set_count(main.count() + 1);
+        }
+
+        Ok(SuspendedRequest {
+            // SAFETY: the pointer is derived from a reference.
+            request: unsafe { NonNull::new_unchecked(r) },
+            content,
+            released: false,
+            freed,
+        })
+    }
+
+    /// Is the request being handled in the content phase?
+    fn in_content_phase(&self) -> bool {
+        let cmcf = self.get_module_main_conf::<ngx_http_core_main_conf_t>(unsafe { &*addr_of!(ngx_http_core_module) });
+        let Some(cmcf) = cmcf else {
+            return false;
+        };
+
+        let index = self.get_inner().phase_handler;
+        if cmcf.phase_engine.handlers.is_null() || index < 0 {
+            return false;
+        }
+
+        // SAFETY: the phase handler index of a request being processed points to the phase
+        // engine array.
+        let checker = unsafe { (*cmcf.phase_engine.handlers.offset(index)).checker };
+        checker.is_some_and(|f| f as *const () == ngx_http_core_content_phase as *const ())
+    }
+}
+
+/// A handle to the request suspended with [`Request::suspend`].
+///
+/// The handle is bound to the worker thread, and should be resumed with [`SuspendedRequest::resume`]
+/// or [`SuspendedRequest::finalize`] once the pending work is complete. Dropping the handle
+/// finalizes the request with `500 Internal Server Error`.
+///
+/// Use [`SuspendedRequest::into_remote`] to resume the request from another thread.
+pub struct SuspendedRequest {
+    request: NonNull<ngx_http_request_t>,
+    content: bool,
+    released: bool,
+    freed: Rc<Cell<bool>>,
+}
+
+impl SuspendedRequest {
+    /// The suspended request, or `None` if the request was terminated.
+    pub fn request(&self) -> Option<&Request> {
+        if self.freed.get() {
+            return None;
+        }
+        // SAFETY: the request is not freed while the reference is held, unless terminated.
+        Some(unsafe { Request::from_ngx_http_request(self.request.as_ptr()) })
+    }
+
+    /// Mutable reference to the suspended request, or `None` if the request was terminated.
+    pub fn request_mut(&mut self) -> Option<&mut Request> {
+        if self.freed.get() {
+            return None;
+        }
+        // SAFETY: the request is not freed while the reference is held, unless terminated.
+        Some(unsafe { Request::from_ngx_http_request(self.request.as_ptr()) })
+    }
+
+    /// Resumes the request processing.
+    ///
+    /// The phase handler or the content handler that suspended the request is invoked again, and
+    /// the request is then processed according to the status returned from the handler.
+    pub fn resume(mut self) {
+        let Some(r) = self.release() else {
+            return;
+        };
+
+        unsafe {
+            let c = (*r).connection;
+            ngx_http_core_run_phases(r);
+            ngx_http_run_posted_requests(c);
+        }
+    }
+
+    /// Finalizes the request with the status, as if it was returned from a content handler.
+    ///
+    /// E.g. `NGX_DONE` once the response is sent with [`Request::output_filter`], `NGX_ERROR` to
+    /// terminate the request, or an HTTP status code to send a special response.
+    pub fn finalize(mut self, rc: Status) {
+        self.finalize_request(rc);
+    }
+
+    /// Converts the handle into a [`RemoteRequest`] that can be resumed from other threads with
+    /// the `notifier`.
+    #[cfg(feature = "async")]
+    pub fn into_remote(self, notifier: Notifier) -> RemoteRequest {
+        RemoteRequest {
+            request: Some(SendRequest(self)),
+            notifier,
+            thread: thread::current().id(),
+        }
+    }
+
+    /// Releases the reference to the request, unless it was already released by NGINX when the
+    /// content phase handler returned `NGX_DONE`.
+    ///
+    /// Returns `None` if the reference was already released, or the request was terminated.
+    fn release(&mut self) -> Option<*mut ngx_http_request_t> {
+        if self.released || self.freed.get() {
+            return None;
+        }
+        self.released = true;
+
+        let r = self.request.as_ptr();
+        if !self.content {
+            unsafe {
+                let main = &mut *(*r).main;
+                main.set_count(main.count() - 1);
+            }
+        }
+        Some(r)
+    }
+
+    fn finalize_request(&mut self, rc: Status) {
+        let Some(r) = self.release() else {
+            return;
+        };
+
+        unsafe {
+            let c = (*r).connection;
+            ngx_http_finalize_request(r, rc.into());
+            ngx_http_run_posted_requests(c);
+        }
+    }
+}
+
+impl Drop for SuspendedRequest {
+    fn drop(&mut self) {
+        self.finalize_request(HTTPStatus::INTERNAL_SERVER_ERROR.into());
+    }
+}
+
+/// Invalidates the suspended request when the request pool is destroyed.
+struct RequestGuard(Rc<Cell<bool>>);
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+/// A handle to the suspended request that can be moved to other threads.
+///
+/// The request is resumed or finalized on the worker thread, with the closure scheduled by the
+/// [`Notifier`]. Dropping the handle finalizes the request with `500 Internal Server Error`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let remote = request.suspend()?.into_remote(Notifier::new()?);
+///
+/// std::thread::spawn(move || {
+///     compute();
+///     let _ = remote.resume();
+/// });
+///
+/// return Status::NGX_DONE;
+/// ```
+#[cfg(feature = "async")]
+pub struct RemoteRequest {
+    request: Option<SendRequest>,
+    notifier: Notifier,
+    thread: ThreadId,
+}
+
+#[cfg(feature = "async")]
+impl RemoteRequest {
+    /// Resumes the request processing on the worker thread.
+    ///
+    /// See [`SuspendedRequest::resume`]. The errors are the same as for [`Notifier::notify`].
+    pub fn resume(mut self) -> Result<(), Status> {
+        self.send(SuspendedRequest::resume)
+    }
+
+    /// Finalizes the request with the status on the worker thread.
+    ///
+    /// See [`SuspendedRequest::finalize`]. The errors are the same as for [`Notifier::notify`].
+    pub fn finalize(mut self, rc: Status) -> Result<(), Status> {
+        self.send(move |request| request.finalize(rc))
+    }
+
+    /// Passes the suspended request to the closure on the worker thread.
+    ///
+    /// The closure is invoked immediately if called from the worker thread. Otherwise, the closure
+    /// remains queued if the notification fails, and is invoked with the next successful
+    /// notification.
+    fn send<F>(&mut self, f: F) -> Result<(), Status>
+    where
+        F: FnOnce(SuspendedRequest) + Send + 'static,
+    {
+        let Some(request) = self.request.take() else {
+            return Ok(());
+        };
+
+        if thread::current().id() == self.thread {
+            f(request.into_inner());
+            return Ok(());
+        }
+
+        self.notifier.notify(move || f(request.into_inner()))
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for RemoteRequest {
+    fn drop(&mut self) {
+        // A failed notification is retried by the following notifications.
+        let _ = self.send(drop);
+    }
+}
+
+/// The suspended request, only accessed on the worker thread.
+#[cfg(feature = "async")]
+struct SendRequest(SuspendedRequest);
+
+// SAFETY: the request is only unwrapped by the closures invoked on the worker thread.
+#[cfg(feature = "async")]
+unsafe impl Send for SendRequest {}
+
+#[cfg(feature = "async")]
+impl SendRequest {
+    fn into_inner(self) -> SuspendedRequest {
+        self.0
+    }
+}
@@ -1,3 +1,11 @@
+use core::ffi::c_void;
+use core::ptr::{self, addr_of};
+use core::slice;
+
+use crate::core::{Buffer, ConfError, NgxStr, Status, TemporaryBuffer};
+use crate::ffi::*;
+use crate::http::{ngx_http_conf_get_module_srv_conf, ngx_http_conf_upstream_srv_conf_mutable, HTTPStatus, Request};
+
 /// Define a static upstream peer initializer
 ///
 /// Initializes the upstream 'get', 'free', and 'session' callbacks and gives the module writer an
@@ -24,3 +32,732 @@ macro_rules! http_upstream_init_peer_pt {
         }
     };
 }
+
+/// Wrapper struct for an [`ngx_peer_connection_t`], the connection to an upstream peer.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#http_load_balancing>
+#[repr(transparent)]
+pub struct PeerConnection(ngx_peer_connection_t);
+
+impl PeerConnection {
+    /// Create a [`PeerConnection`] from an [`ngx_peer_connection_t`].
+    ///
+    /// # Safety
+    ///
+    /// The caller has provided a valid non-null pointer to a valid `ngx_peer_connection_t`
+    /// which shares the same representation as `PeerConnection`.
+    pub unsafe fn from_ngx_peer_connection<'a>(pc: *mut ngx_peer_connection_t) -> &'a mut PeerConnection {
+        &mut *pc.cast::<PeerConnection>()
+    }
+
+    /// Returns a raw pointer to the underlying [`ngx_peer_connection_t`].
+    pub fn as_ptr(&mut self) -> *mut ngx_peer_connection_t {
+        &mut self.0
+    }
+
+    /// Connection to the peer, established after the peer is selected.
+    pub fn connection(&self) -> *mut ngx_connection_t {
+        self.0.connection
+    }
+
+    /// Address of the selected peer, set by the balancer in [`LoadBalancer::get_peer`].
+    pub fn sockaddr(&self) -> Option<&sockaddr> {
+        unsafe { self.0.sockaddr.as_ref() }
+    }
+
+    /// Length of the [`PeerConnection::sockaddr`].
+    pub fn socklen(&self) -> socklen_t {
+        self.0.socklen
+    }
+
+    /// Name of the selected peer, set by the balancer in [`LoadBalancer::get_peer`].
+    pub fn name(&self) -> Option<&NgxStr> {
+        let name = unsafe { self.0.name.as_ref()? };
+        Some(unsafe { NgxStr::from_ngx_str(*name) })
+    }
+
+    /// Number of the remaining attempts to connect to a peer.
+    pub fn tries(&self) -> ngx_uint_t {
+        self.0.tries
+    }
+
+    /// Time of the first attempt to connect to a peer, in milliseconds.
+    pub fn start_time(&self) -> ngx_msec_t {
+        self.0.start_time
+    }
+
+    /// Pointer to a [`ngx_log_t`].
+    ///
+    /// [`ngx_log_t`]: https://nginx.org/en/docs/dev/development_guide.html#logging
+    pub fn log(&self) -> *mut ngx_log_t {
+        self.0.log
+    }
+}
+
+/// The balancer handlers configured for an upstream before a [`LoadBalancer`] was enabled,
+/// usually the round-robin balancer.
+///
+/// The value is stored in the server configuration of the module implementing the balancer, see
+/// [`LoadBalancer::original_balancer`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OriginalBalancer {
+    init_upstream: ngx_http_upstream_init_pt,
+    init_peer: ngx_http_upstream_init_peer_pt,
+}
+
+/// The peer selection callbacks of the original balancer for the current request.
+///
+/// The [`LoadBalancer`] may delegate the peer selection to the original balancer, e.g. to reuse
+/// the round-robin handling of the `server` weights, failures and backup servers.
+pub struct Fallback {
+    data: *mut c_void,
+    get: ngx_event_get_peer_pt,
+    free: ngx_event_free_peer_pt,
+    #[cfg(any(ngx_feature = "ssl", ngx_feature = "compat"))]
+    set_session: ngx_event_set_peer_session_pt,
+    #[cfg(any(ngx_feature = "ssl", ngx_feature = "compat"))]
+    save_session: ngx_event_save_peer_session_pt,
+}
+
+impl Fallback {
+    /// Selects a peer with the original balancer.
+    pub fn get_peer(&self, pc: &mut PeerConnection) -> Status {
+        match self.get {
+            Some(get) => Status(unsafe { get(pc.as_ptr(), self.data) }),
+            None => Status::NGX_ERROR,
+        }
+    }
+
+    /// Releases the peer with the original balancer.
+    pub fn free_peer(&self, pc: &mut PeerConnection, state: ngx_uint_t) {
+        if let Some(free) = self.free {
+            unsafe { free(pc.as_ptr(), self.data, state) }
+        }
+    }
+}
+
+/// An upstream load balancer.
+///
+/// The balancer is enabled for an `upstream` block with [`set_load_balancer`], usually from a
+/// directive handler. The crate wraps the balancer configured before, i.e. the round-robin one by
+/// default, which is initialized first and is available as a [`Fallback`].
+///
+/// The implementing type holds the per-request state, created by [`LoadBalancer::init_peer`] and
+/// allocated from the request pool. The state is dropped with the request.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#http_load_balancing>
+///
+/// # Example
+///
+/// ```rust,ignore
+/// struct Custom { tries: usize }
+///
+/// impl LoadBalancer for Custom {
+///     type SrvConf = SrvConfig;
+///
+///     fn module() -> &'static ngx_module_t {
+///         unsafe { &*addr_of!(ngx_http_upstream_custom_module) }
+///     }
+///
+///     fn original_balancer(conf: &mut SrvConfig) -> &mut OriginalBalancer {
+///         &mut conf.original
+///     }
+///
+///     fn init_peer(_request: &mut Request, _conf: &'static SrvConfig) -> Result<Self, Status> {
+///         Ok(Custom { tries: 0 })
+///     }
+///
+///     fn get_peer(&mut self, pc: &mut PeerConnection, fallback: &Fallback) -> Status {
+///         self.tries += 1;
+///         fallback.get_peer(pc)
+///     }
+/// }
+/// ```
+pub trait LoadBalancer: Sized {
+    /// Server configuration of the module in the `upstream` block.
+    type SrvConf: 'static;
+
+    /// The module implementing the balancer.
+    fn module() -> &'static ngx_module_t;
+
+    /// Returns the storage for the original balancer handlers in the module configuration.
+    fn original_balancer(conf: &mut Self::SrvConf) -> &mut OriginalBalancer;
+
+    /// Initializes the balancer for the `upstream` block at the configuration time.
+    ///
+    /// Invoked after the original balancer is initialized.
+    fn init_upstream(
+        _cf: &mut ngx_conf_t,
+        _us: &mut ngx_http_upstream_srv_conf_t,
+        _conf: &mut Self::SrvConf,
+    ) -> Result<(), Status> {
+        Ok(())
+    }
+
+    /// Creates the per-request balancer state.
+    ///
+    /// Invoked after the original balancer is initialized for the request.
+    fn init_peer(request: &mut Request, conf: &'static Self::SrvConf) -> Result<Self, Status>;
+
+    /// Selects a peer for the connection attempt.
+    ///
+    /// The default implementation delegates to the original balancer.
+    fn get_peer(&mut self, pc: &mut PeerConnection, fallback: &Fallback) -> Status {
+        fallback.get_peer(pc)
+    }
+
+    /// Releases the peer after the connection attempt, with the `state` of `NGX_PEER_FAILED` or
+    /// `NGX_PEER_NEXT` if it was unsuccessful.
+    ///
+    /// The default implementation delegates to the original balancer.
+    fn free_peer(&mut self, pc: &mut PeerConnection, state: ngx_uint_t, fallback: &Fallback) {
+        fallback.free_peer(pc, state)
+    }
+}
+
+/// Enables the load balancer `B` for the `upstream` block being configured.
+///
+/// The `conf` is the server configuration of the balancer module in the `upstream` block.
+pub fn set_load_balancer<B: LoadBalancer>(cf: &mut ngx_conf_t, conf: &mut B::SrvConf) -> Result<(), ConfError> {
+    // SAFETY: the directive handlers of the `upstream` block are called with the HTTP
+    // configuration context.
+    let uscf = unsafe {
+        ngx_http_conf_get_module_srv_conf(cf, &*addr_of!(ngx_http_upstream_module))
+            .cast::<ngx_http_upstream_srv_conf_t>()
+            .as_mut()
+    };
+    let Some(uscf) = uscf else {
+        return Err(ConfError::Message(c"is not allowed here"));
+    };
+
+    let original = B::original_balancer(conf);
+    original.init_upstream = match uscf.peer.init_upstream {
+        Some(init) => Some(init),
+        None => Some(ngx_http_upstream_init_round_robin),
+    };
+
+    uscf.peer.init_upstream = Some(init_upstream_handler::<B>);
+    Ok(())
+}
+
+struct PeerData<B> {
+    balancer: B,
+    fallback: Fallback,
+}
+
+unsafe extern "C" fn init_upstream_handler<B: LoadBalancer>(
+    cf: *mut ngx_conf_t,
+    us: *mut ngx_http_upstream_srv_conf_t,
+) -> ngx_int_t {
+    let Some(conf) = ngx_http_conf_upstream_srv_conf_mutable::<B::SrvConf>(us, B::module()) else {
+        return Status::NGX_ERROR.into();
+    };
+    let conf = &mut *conf;
+    let original = B::original_balancer(conf);
+
+    let rc = match original.init_upstream {
+        Some(init) => init(cf, us),
+        None => Status::NGX_ERROR.into(),
+    };
+    if rc != Status::NGX_OK.into() {
+        return rc;
+    }
+
+    original.init_peer = (*us).peer.init;
+    (*us).peer.init = Some(init_peer_handler::<B>);
+
+    match B::init_upstream(&mut *cf, &mut *us, conf) {
+        Ok(()) => Status::NGX_OK.into(),
+        Err(status) => status.into(),
+    }
+}
+
+unsafe extern "C" fn init_peer_handler<B: LoadBalancer>(
+    r: *mut ngx_http_request_t,
+    us: *mut ngx_http_upstream_srv_conf_t,
+) -> ngx_int_t {
+    let Some(conf) = ngx_http_conf_upstream_srv_conf_mutable::<B::SrvConf>(us, B::module()) else {
+        return Status::NGX_ERROR.into();
+    };
+    let original = *B::original_balancer(&mut *conf);
+    let conf: &'static B::SrvConf = &*conf;
+
+    let rc = match original.init_peer {
+        Some(init) => init(r, us),
+        None => Status::NGX_ERROR.into(),
+    };
+    if rc != Status::NGX_OK.into() {
+        return rc;
+    }
+
+    let request = Request::from_ngx_http_request(r);
+    let u = (*r).upstream;
+    if u.is_null() {
+        return Status::NGX_ERROR.into();
+    }
+    let balancer = match B::init_peer(request, conf) {
+        Ok(balancer) => balancer,
+        Err(status) => return status.into(),
+    };
+
+    let peer = &mut (*u).peer;
+    let data = request.pool().allocate(PeerData {
+        balancer,
+        fallback: Fallback {
+            data: peer.data,
+            get: peer.get,
+            free: peer.free,
+            #[cfg(any(ngx_feature = "ssl", ngx_feature = "compat"))]
+            set_session: peer.set_session,
+            #[cfg(any(ngx_feature = "ssl", ngx_feature = "compat"))]
+            save_session: peer.save_session,
+        },
+    });
+    if data.is_null() {
+        return Status::NGX_ERROR.into();
+    }
+
+    peer.data = data.cast();
+    peer.get = Some(get_peer_handler::<B>);
+    peer.free = Some(free_peer_handler::<B>);
+
+    #[cfg(any(ngx_feature = "ssl", ngx_feature = "compat"))]
+    {
+        peer.set_session = Some(set_session_handler::<B>);
+        peer.save_session = Some(save_session_handler::<B>);
+    }
+
+    Status::NGX_OK.into()
+}
+
+unsafe extern "C" fn get_peer_handler<B: LoadBalancer>(pc: *mut ngx_peer_connection_t, data: *mut c_void) -> ngx_int_t {
+    let data = &mut *data.cast::<PeerData<B>>();
+    let pc = PeerConnection::from_ngx_peer_connection(pc);
+    data.balancer.get_peer(pc, &data.fallback).into()
+}
+
+unsafe extern "C" fn free_peer_handler<B: LoadBalancer>(
+    pc: *mut ngx_peer_connection_t,
+    data: *mut c_void,
+    state: ngx_uint_t,
+) {
+    let data = &mut *data.cast::<PeerData<B>>();
+    let pc = PeerConnection::from_ngx_peer_connection(pc);
+    data.balancer.free_peer(pc, state, &data.fallback)
+}
+
+#[cfg(any(ngx_feature = "ssl", ngx_feature = "compat"))]
+unsafe extern "C" fn set_session_handler<B: LoadBalancer>(
+    pc: *mut ngx_peer_connection_t,
+    data: *mut c_void,
+) -> ngx_int_t {
+    let data = &*data.cast::<PeerData<B>>();
+    match data.fallback.set_session {
+        Some(set_session) => set_session(pc, data.fallback.data),
+        None => Status::NGX_OK.into(),
+    }
+}
+
+#[cfg(any(ngx_feature = "ssl", ngx_feature = "compat"))]
+unsafe extern "C" fn save_session_handler<B: LoadBalancer>(pc: *mut ngx_peer_connection_t, data: *mut c_void) {
+    let data = &*data.cast::<PeerData<B>>();
+    if let Some(save_session) = data.fallback.save_session {
+        save_session(pc, data.fallback.data)
+    }
+}
+
+/// Wrapper struct for an [`ngx_http_upstream_t`], the state of the request to an upstream server.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#http_load_balancing>
+#[repr(transparent)]
+pub struct Upstream(ngx_http_upstream_t);
+
+impl Upstream {
+    /// Create an [`Upstream`] from an [`ngx_http_upstream_t`].
+    ///
+    /// # Safety
+    ///
+    /// The caller has provided a valid non-null pointer to a valid `ngx_http_upstream_t`
+    /// which shares the same representation as `Upstream`.
+    pub unsafe fn from_ngx_http_upstream<'a>(u: *mut ngx_http_upstream_t) -> &'a mut Upstream {
+        &mut *u.cast::<Upstream>()
+    }
+
+    /// Returns a raw pointer to the underlying [`ngx_http_upstream_t`].
+    pub fn as_ptr(&mut self) -> *mut ngx_http_upstream_t {
+        &mut self.0
+    }
+
+    /// Connection to the upstream peer.
+    pub fn peer(&self) -> &PeerConnection {
+        unsafe { &*ptr::addr_of!(self.0.peer).cast::<PeerConnection>() }
+    }
+
+    /// Mutable reference to the connection to the upstream peer.
+    pub fn peer_mut(&mut self) -> &mut PeerConnection {
+        unsafe { PeerConnection::from_ngx_peer_connection(&mut self.0.peer) }
+    }
+
+    /// State of the current attempt to connect to an upstream peer.
+    ///
+    /// The state of all the attempts is available with [`Request::upstream_states`].
+    pub fn state(&self) -> Option<&UpstreamState> {
+        unsafe { self.0.state.cast::<UpstreamState>().as_ref() }
+    }
+
+    /// Status of the upstream response, or `None` if the response header is not processed yet.
+    pub fn status(&self) -> Option<HTTPStatus> {
+        let status = self.0.headers_in.status_n;
+        (status != 0).then_some(HTTPStatus(status))
+    }
+
+    /// Length of the upstream response body, or `None` if unknown.
+    pub fn content_length(&self) -> Option<off_t> {
+        let length = self.0.headers_in.content_length_n;
+        (length >= 0).then_some(length)
+    }
+
+    /// The received and not yet processed part of the upstream response.
+    pub fn buffer(&self) -> &[u8] {
+        let buf = &self.0.buffer;
+        if buf.pos.is_null() || buf.last <= buf.pos {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(buf.pos, buf.last.offset_from(buf.pos) as usize) }
+    }
+
+    /// Marks the first `n` bytes of the [`Upstream::buffer`] as processed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is larger than the buffer length.
+    pub fn consume(&mut self, n: usize) {
+        assert!(n <= self.buffer().len());
+        self.0.buffer.pos = unsafe { self.0.buffer.pos.add(n) };
+    }
+
+    /// Sets the status of the upstream response.
+    pub fn set_status(&mut self, status: HTTPStatus) {
+        self.0.headers_in.status_n = status.0;
+        if let Some(state) = unsafe { self.0.state.as_mut() } {
+            state.status = status.0;
+        }
+    }
+
+    /// Sets the length of the upstream response body, or `None` if unknown.
+    pub fn set_content_length(&mut self, length: Option<off_t>) {
+        self.0.headers_in.content_length_n = length.unwrap_or(-1);
+    }
+
+    /// Remaining length of the upstream response, or `None` if the response continues until the
+    /// connection is closed.
+    pub fn length(&self) -> Option<off_t> {
+        (self.0.length >= 0).then_some(self.0.length)
+    }
+
+    /// Sets the remaining length of the upstream response, including any protocol framing.
+    ///
+    /// The request to the upstream is complete when the length reaches zero.
+    pub fn set_length(&mut self, length: Option<off_t>) {
+        self.0.length = length.unwrap_or(-1);
+    }
+}
+
+/// Wrapper struct for an [`ngx_http_upstream_state_t`], the state of an attempt to connect to an
+/// upstream peer.
+///
+/// The times are only available after the attempt is finished.
+#[repr(transparent)]
+pub struct UpstreamState(ngx_http_upstream_state_t);
+
+impl UpstreamState {
+    /// Status of the upstream response, or `None` if no response was received.
+    pub fn status(&self) -> Option<HTTPStatus> {
+        (self.0.status != 0).then_some(HTTPStatus(self.0.status))
+    }
+
+    /// Address of the upstream peer, or `None` if the peer was not selected.
+    pub fn peer(&self) -> Option<&NgxStr> {
+        let peer = unsafe { self.0.peer.as_ref()? };
+        Some(unsafe { NgxStr::from_ngx_str(*peer) })
+    }
+
+    /// Time spent receiving the response from the upstream server, in milliseconds.
+    pub fn response_time(&self) -> ngx_msec_t {
+        self.0.response_time
+    }
+
+    /// Time spent establishing the connection with the upstream server, in milliseconds, or `None`
+    /// if the connection was not established.
+    pub fn connect_time(&self) -> Option<ngx_msec_t> {
+        (self.0.connect_time != ngx_msec_t::MAX).then_some(self.0.connect_time)
+    }
+
+    /// Time spent receiving the response header from the upstream server, in milliseconds, or
+    /// `None` if the header was not received.
+    pub fn header_time(&self) -> Option<ngx_msec_t> {
+        (self.0.header_time != ngx_msec_t::MAX).then_some(self.0.header_time)
+    }
+
+    /// Length of the response received from the upstream server, in bytes.
+    pub fn response_length(&self) -> off_t {
+        self.0.response_length
+    }
+
+    /// Number of bytes received from the upstream server.
+    pub fn bytes_received(&self) -> off_t {
+        self.0.bytes_received
+    }
+
+    /// Number of bytes sent to the upstream server.
+    pub fn bytes_sent(&self) -> off_t {
+        self.0.bytes_sent
+    }
+}
+
+/// A protocol for the requests to upstream servers.
+///
+/// The protocol is started with [`start_upstream`] from a content handler. The implementing type
+/// holds the per-request protocol state, e.g. the response parser. It is allocated from the
+/// request pool and dropped with the request.
+///
+/// The response is sent to the client unbuffered, as received. Each part of the response body is
+/// passed to [`UpstreamProtocol::input_filter`], which selects the data for the client.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#http_load_balancing>
+///
+/// # Example
+///
+/// ```rust,ignore
+/// struct Echo;
+///
+/// impl UpstreamProtocol for Echo {
+///     const SCHEMA: &'static str = "echo://";
+///
+///     fn module() -> &'static ngx_module_t {
+///         unsafe { &*addr_of!(ngx_http_echo_module) }
+///     }
+///
+///     fn create_request(&mut self, request: &mut Request, _u: &mut Upstream) -> Result<TemporaryBuffer, Status> {
+///         request.pool().create_buffer_from_str("PING\r\n").ok_or(Status::NGX_ERROR)
+///     }
+///
+///     fn process_header(&mut self, _request: &mut Request, u: &mut Upstream) -> Status {
+///         let Some(n) = u.buffer().iter().position(|&c| c == b'\n') else {
+///             return Status::NGX_AGAIN;
+///         };
+///         u.consume(n + 1);
+///         u.set_status(HTTPStatus::OK);
+///         Status::NGX_OK
+///     }
+/// }
+/// ```
+pub trait UpstreamProtocol: Sized {
+    /// Name of the protocol, used in the log messages, e.g. `memcached://`.
+    const SCHEMA: &'static str = "";
+
+    /// The module implementing the protocol.
+    fn module() -> &'static ngx_module_t;
+
+    /// Creates the request to the upstream server.
+    fn create_request(&mut self, request: &mut Request, upstream: &mut Upstream) -> Result<TemporaryBuffer, Status>;
+
+    /// Resets the protocol state before the request is retried with the next upstream server.
+    fn reinit_request(&mut self, _request: &mut Request, _upstream: &mut Upstream) -> Result<(), Status> {
+        Ok(())
+    }
+
+    /// Processes the response header in the [`Upstream::buffer`].
+    ///
+    /// Returns `NGX_OK` when the header is complete, `NGX_AGAIN` if more data is needed, or
+    /// `NGX_HTTP_UPSTREAM_INVALID_HEADER` if the response is invalid. The processed part of the
+    /// buffer is marked with [`Upstream::consume`], and the rest is passed to the
+    /// [`UpstreamProtocol::input_filter`].
+    fn process_header(&mut self, request: &mut Request, upstream: &mut Upstream) -> Status;
+
+    /// Prepares the processing of the response body.
+    fn input_filter_init(&mut self, _request: &mut Request, _upstream: &mut Upstream) -> Result<(), Status> {
+        Ok(())
+    }
+
+    /// Processes a part of the response body.
+    ///
+    /// Returns the number of bytes from the start of the `data` to send to the client. The default
+    /// implementation sends all the data.
+    fn input_filter(&mut self, _request: &mut Request, _upstream: &mut Upstream, data: &[u8]) -> Result<usize, Status> {
+        Ok(data.len())
+    }
+
+    /// Invoked when the request to the upstream is finished, with the final status `rc`.
+    fn finalize_request(&mut self, _request: &mut Request, _upstream: &mut Upstream, _rc: ngx_int_t) {}
+}
+
+/// Starts the request to the upstream server with the protocol `P`.
+///
+/// Intended to be called from a content handler, which returns the resulting status. The `conf`
+/// specifies the upstream servers and the timeouts, where the `upstream` field is usually set
+/// with `ngx_http_upstream_add` from a directive handler.
+///
+/// The client request body is discarded before the upstream request is started, as with the
+/// `memcached` module, so the [`UpstreamProtocol::create_request`] cannot forward it.
+pub fn start_upstream<P: UpstreamProtocol>(
+    request: &mut Request,
+    conf: &'static ngx_http_upstream_conf_t,
+    protocol: P,
+) -> Status {
+    let rc = request.discard_request_body();
+    if rc != Status::NGX_OK {
+        return rc;
+    }
+
+    let r: *mut ngx_http_request_t = request.into();
+
+    if unsafe { ngx_http_upstream_create(r) } != Status::NGX_OK.into() {
+        return HTTPStatus::INTERNAL_SERVER_ERROR.into();
+    }
+
+    let data = request.pool().allocate(UpstreamData { protocol, request: r });
+    if data.is_null() {
+        return HTTPStatus::INTERNAL_SERVER_ERROR.into();
+    }
+
+    unsafe {
+        let u = &mut *(*r).upstream;
+
+        u.schema = ngx_str_t {
+            len: P::SCHEMA.len(),
+            data: P::SCHEMA.as_ptr().cast_mut(),
+        };
+        u.output.tag = (P::module() as *const ngx_module_t).cast_mut().cast();
+        u.conf = (conf as *const ngx_http_upstream_conf_t).cast_mut();
+
+        u.create_request = Some(create_request_handler::<P>);
+        u.reinit_request = Some(reinit_request_handler::<P>);
+        u.process_header = Some(process_header_handler::<P>);
+        u.abort_request = Some(abort_request_handler);
+        u.finalize_request = Some(finalize_request_handler::<P>);
+
+        u.input_filter_init = Some(input_filter_init_handler::<P>);
+        u.input_filter = Some(input_filter_handler::<P>);
+        u.input_filter_ctx = data.cast();
+
+        let main = &mut *(*r).main;
+        main.set_count(main.count() + 1);
+
+        ngx_http_upstream_init(r);
+    }
+
+    Status::NGX_DONE
+}
+
+struct UpstreamData<P> {
+    protocol: P,
+    request: *mut ngx_http_request_t,
+}
+
+/// # Safety
+///
+/// The request has an upstream started with [`start_upstream`] for the same protocol.
+unsafe fn upstream_data<'a, P>(r: *mut ngx_http_request_t) -> (&'a mut P, &'a mut Request, &'a mut Upstream) {
+    let u = (*r).upstream;
+    let data = &mut *(*u).input_filter_ctx.cast::<UpstreamData<P>>();
+    (
+        &mut data.protocol,
+        Request::from_ngx_http_request(r),
+        Upstream::from_ngx_http_upstream(u),
+    )
+}
+
+unsafe extern "C" fn create_request_handler<P: UpstreamProtocol>(r: *mut ngx_http_request_t) -> ngx_int_t {
+    let (protocol, request, upstream) = upstream_data::<P>(r);
+
+    let mut buf = match protocol.create_request(request, upstream) {
+        Ok(buf) => buf,
+        Err(status) => return status.into(),
+    };
+
+    let cl = ngx_alloc_chain_link((*r).pool);
+    if cl.is_null() {
+        return Status::NGX_ERROR.into();
+    }
+
+    (*cl).buf = buf.as_ngx_buf_mut();
+    (*cl).next = ptr::null_mut();
+    (*(*r).upstream).request_bufs = cl;
+
+    Status::NGX_OK.into()
+}
+
+unsafe extern "C" fn reinit_request_handler<P: UpstreamProtocol>(r: *mut ngx_http_request_t) -> ngx_int_t {
+    let (protocol, request, upstream) = upstream_data::<P>(r);
+    match protocol.reinit_request(request, upstream) {
+        Ok(()) => Status::NGX_OK.into(),
+        Err(status) => status.into(),
+    }
+}
+
+unsafe extern "C" fn process_header_handler<P: UpstreamProtocol>(r: *mut ngx_http_request_t) -> ngx_int_t {
+    let (protocol, request, upstream) = upstream_data::<P>(r);
+    protocol.process_header(request, upstream).into()
+}
+
+unsafe extern "C" fn abort_request_handler(_r: *mut ngx_http_request_t) {}
+
+unsafe extern "C" fn finalize_request_handler<P: UpstreamProtocol>(r: *mut ngx_http_request_t, rc: ngx_int_t) {
+    let (protocol, request, upstream) = upstream_data::<P>(r);
+    protocol.finalize_request(request, upstream, rc)
+}
+
+unsafe extern "C" fn input_filter_init_handler<P: UpstreamProtocol>(data: *mut c_void) -> ngx_int_t {
+    let data = &*data.cast::<UpstreamData<P>>();
+    let (protocol, request, upstream) = upstream_data::<P>(data.request);
+    match protocol.input_filter_init(request, upstream) {
+        Ok(()) => Status::NGX_OK.into(),
+        Err(status) => status.into(),
+    }
+}
+
+unsafe extern "C" fn input_filter_handler<P: UpstreamProtocol>(data: *mut c_void, bytes: isize) -> ngx_int_t {
+    let data = &*data.cast::<UpstreamData<P>>();
+    let r = data.request;
+
+    // The received data is placed right after the end of the buffer contents.
+    let received = slice::from_raw_parts((*(*r).upstream).buffer.last, bytes as usize);
+
+    let (protocol, request, upstream) = upstream_data::<P>(r);
+    let n = match protocol.input_filter(request, upstream, received) {
+        Ok(n) => n.min(received.len()),
+        Err(status) => return status.into(),
+    };
+
+    let u = &mut *upstream.as_ptr();
+
+    if n > 0 {
+        let mut ll = &mut u.out_bufs;
+        while let Some(cl) = ll.as_mut() {
+            ll = &mut cl.next;
+        }
+
+        let cl = ngx_chain_get_free_buf((*r).pool, &mut u.free_bufs);
+        if cl.is_null() {
+            return Status::NGX_ERROR.into();
+        }
+        *ll = cl;
+
+        let b = &mut *(*cl).buf;
+        b.set_flush(1);
+        b.set_memory(1);
+        b.pos = u.buffer.last;
+        b.last = u.buffer.last.add(n);
+        b.tag = u.output.tag;
+    }
+
+    u.buffer.last = u.buffer.last.add(bytes as usize);
+
+    if u.length != -1 {
+        u.length = (u.length - bytes as off_t).max(0);
+    }
+
+    Status::NGX_OK.into()
+}
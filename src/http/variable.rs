@@ -0,0 +1,208 @@
+use core::ptr::{self, addr_of};
+use core::slice;
+
+use crate::core::{
+    alloc_str, find_prefix_variable, variable_get_handler, variable_key, variable_prefix_get_handler,
+    variable_set_handler, variable_value, ModuleVariable, NgxStr, Status, VariableContext,
+};
+use crate::ffi::*;
+use crate::http::Request;
+
+pub use crate::core::VariableFlags;
+
+/// Variable getter.
+///
+/// Returns the value of the variable, or `None` if the variable is not found, or an error if the
+/// evaluation failed. The returned slice
+/// must remain valid for the lifetime of the request, e.g. point to the request data or be
+/// allocated from the request pool with [`Request::alloc_str`].
+pub type VariableGetter = for<'r> fn(&'r mut Request) -> Result<Option<&'r [u8]>, Status>;
+
+/// Prefix variable getter, invoked with the rest of the requested variable name.
+///
+/// See [`Variable::prefix_getter`](crate::core::ModuleVariable::prefix_getter).
+pub type VariablePrefixGetter = for<'r, 'n> fn(&'r mut Request, &'n NgxStr) -> Result<Option<&'r [u8]>, Status>;
+
+/// Variable setter, invoked for the changeable variables by the `set` directive.
+pub type VariableSetter = for<'r> fn(&'r mut Request, &'r NgxStr);
+
+/// Definition of an HTTP variable provided by a module.
+///
+/// The variables are registered with [`add_variables`], usually from the
+/// [`HTTPModule::preconfiguration`](crate::http::HTTPModule::preconfiguration) handler, where the
+/// default implementation registers the [`HTTPModule::VARIABLES`](crate::http::HTTPModule::VARIABLES).
+///
+/// # Example
+///
+/// ```rust,ignore
+/// impl HTTPModule for Module {
+///     // ...
+///     const VARIABLES: &'static [Variable] = &[
+///         Variable::new("hello").getter(|_request| Ok(Some(&b"world"[..]))),
+///         Variable::new("request_path")
+///             .flags(VariableFlags::NOCACHEABLE)
+///             .getter(|request| Ok(Some(request.path().as_bytes()))),
+///         // $upper_foo is the uppercase value of the `foo` argument
+///         Variable::new("upper_")
+///             .flags(VariableFlags::NOCACHEABLE)
+///             .prefix_getter(|request, name| {
+///                 let Some(value) = request.arg(name.to_str().unwrap_or_default()) else {
+///                     return Ok(None);
+///                 };
+///                 let value = request.alloc_str(value.as_bytes().to_ascii_uppercase())?;
+///                 Ok(Some(value))
+///             }),
+///     ];
+/// }
+/// ```
+pub type Variable = ModuleVariable<Request>;
+
+impl VariableContext for Request {
+    type Raw = ngx_http_request_t;
+
+    unsafe fn from_raw<'a>(raw: *mut ngx_http_request_t) -> &'a mut Self {
+        Request::from_ngx_http_request(raw)
+    }
+
+    fn prefix_variable(&self, name: &[u8]) -> Option<&'static Variable> {
+        let cmcf =
+            self.get_module_main_conf::<ngx_http_core_main_conf_t>(unsafe { &*addr_of!(ngx_http_core_module) })?;
+        let variables: &[ngx_http_variable_t] = match cmcf.prefix_variables.nelts {
+            0 => &[],
+            // SAFETY: the array contains `ngx_http_variable_t` elements.
+            n => unsafe { slice::from_raw_parts(cmcf.prefix_variables.elts.cast(), n) },
+        };
+
+        find_prefix(variables, name)
+    }
+}
+
+/// Finds the prefix variable registered by the crate, see [`VariableContext::prefix_variable`].
+fn find_prefix(variables: &[ngx_http_variable_t], name: &[u8]) -> Option<&'static Variable> {
+    find_prefix_variable(
+        variables
+            .iter()
+            .map(|v| (&v.name, v.get_handler.map_or(ptr::null(), |f| f as *const ()), v.data)),
+        prefix_get_handler as *const (),
+        name,
+    )
+}
+
+/// The `get_handler` of the prefix variables.
+///
+/// The handler is not generic, so that its address identifies the variables registered by the
+/// crate in [`Request::prefix_variable`](VariableContext::prefix_variable).
+unsafe extern "C" fn prefix_get_handler(
+    r: *mut ngx_http_request_t,
+    v: *mut ngx_variable_value_t,
+    data: usize,
+) -> ngx_int_t {
+    variable_prefix_get_handler::<Request>(r, v, data)
+}
+
+/// Index of a variable, obtained at the configuration time.
+///
+/// Looking up a variable by index avoids the hash lookup of [`Request::variable`] and allows the
+/// values to be cached for the request.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// // in a directive handler or in postconfiguration
+/// let index = VariableIndex::new(cf, "upstream_addr")?;
+///
+/// // in a request handler
+/// if let Some(addr) = request.indexed_variable(index) {
+///     ngx_log_debug_http!(request, "upstream: {}", addr.to_string_lossy());
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VariableIndex(ngx_uint_t);
+
+impl VariableIndex {
+    /// Gets the index of the variable with the given name, without the leading `$`.
+    ///
+    /// The variable does not have to be defined yet, e.g. a variable set later in the configuration
+    /// with the `set` directive. The existence of the variable is checked by NGINX at the end of the
+    /// configuration parsing.
+    pub fn new(cf: &mut ngx_conf_t, name: &str) -> Result<Self, Status> {
+        let mut name = ngx_str_t {
+            len: name.len(),
+            data: name.as_ptr().cast_mut(),
+        };
+
+        let index = unsafe { ngx_http_get_variable_index(cf, &mut name) };
+        if index == NGX_ERROR as ngx_int_t {
+            return Err(Status::NGX_ERROR);
+        }
+
+        Ok(VariableIndex(index as ngx_uint_t))
+    }
+
+    /// Returns the raw index value.
+    pub fn index(&self) -> ngx_uint_t {
+        self.0
+    }
+}
+
+/// Registers the variables with `ngx_http_add_variable`.
+///
+/// Returns `Err(Status::NGX_ERROR)` if the variable cannot be added, e.g. because of a conflict
+/// with an existing variable. The error is logged by NGINX.
+pub fn add_variables(cf: &mut ngx_conf_t, variables: &'static [Variable]) -> Result<(), Status> {
+    for var in variables {
+        let mut name = var.ngx_name();
+
+        let v = unsafe { ngx_http_add_variable(cf, &mut name, var.ngx_flags()) };
+        let Some(v) = (unsafe { v.as_mut() }) else {
+            return Err(Status::NGX_ERROR);
+        };
+
+        if var.is_prefix() {
+            v.get_handler = Some(prefix_get_handler);
+        } else {
+            if var.get.is_some() {
+                v.get_handler = Some(variable_get_handler::<Request>);
+            }
+            if var.set.is_some() {
+                v.set_handler = Some(variable_set_handler::<Request>);
+            }
+        }
+        v.data = var as *const Variable as usize;
+    }
+
+    Ok(())
+}
+
+impl Request {
+    /// Value of the variable `name`, without the leading `$`.
+    ///
+    /// Returns `None` if the variable is unknown, has no value or the evaluation failed. Prefer
+    /// [`Request::indexed_variable`] for the variables known at the configuration time.
+    pub fn variable(&self, name: &str) -> Option<&NgxStr> {
+        let r: *const ngx_http_request_t = self.into();
+
+        let (mut name, key) = variable_key(self.pool(), name)?;
+
+        let v = unsafe { ngx_http_get_variable(r.cast_mut(), &mut name, key) };
+        variable_value(v)
+    }
+
+    /// Value of the variable with the given index.
+    ///
+    /// Returns `None` if the variable has no value or the evaluation failed.
+    pub fn indexed_variable(&self, index: VariableIndex) -> Option<&NgxStr> {
+        let r: *const ngx_http_request_t = self.into();
+        let v = unsafe { ngx_http_get_indexed_variable(r.cast_mut(), index.0) };
+        variable_value(v)
+    }
+
+    /// Copies the string to the request pool.
+    ///
+    /// The returned slice remains valid for the lifetime of the request, which makes it suitable
+    /// for the [`VariableGetter`] values. Returns `Err(Status::NGX_ERROR)` if the memory cannot be
+    /// allocated.
+    pub fn alloc_str(&self, value: impl AsRef<[u8]>) -> Result<&[u8], Status> {
+        alloc_str(self.pool(), value.as_ref())
+    }
+}
@@ -40,6 +40,13 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
+/// The async module.
+///
+/// This module provides an executor for Rust futures, driven by the NGINX event loop of the worker
+/// process. Available with the `async` feature.
+#[cfg(feature = "async")]
+pub mod async_;
+
 /// The core module.
 ///
 /// This module provides fundamental utilities needed to interface with many NGINX primitives.
@@ -58,6 +65,20 @@ pub mod ffi;
 /// configuration access, and statuses.
 pub mod http;
 
+/// The mail module.
+///
+/// This module provides wrappers and utilities to NGINX mail proxy APIs, such as sessions and
+/// module configuration. Available if NGINX is built with `--with-mail`.
+#[cfg(ngx_feature = "mail")]
+pub mod mail;
+
+/// The stream module.
+///
+/// This module provides wrappers and utilities to NGINX stream (TCP/UDP) APIs, such as sessions,
+/// processing phases and module configuration. Available if NGINX is built with `--with-stream`.
+#[cfg(ngx_feature = "stream")]
+pub mod stream;
+
 /// The log module.
 ///
 /// This module provides an interface into the NGINX logger framework.
@@ -96,5 +117,5 @@ macro_rules! ngx_modules {
 #[macro_export]
 macro_rules! count {
     () => { 0usize };
-    ($x:tt, $( $xs:tt ),*) => { 1usize + $crate::count!($( $xs, )*) };
+    ($x:tt $(, $xs:tt )* $(,)?) => { 1usize + $crate::count!($( $xs ),*) };
 }
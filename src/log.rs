@@ -130,6 +130,28 @@ macro_rules! ngx_log_debug_http {
     }
 }
 
+/// Log to mail session connection log at level [`NGX_LOG_DEBUG_MAIL`].
+///
+/// [`NGX_LOG_DEBUG_MAIL`]: https://nginx.org/en/docs/dev/development_guide.html#logging
+#[macro_export]
+macro_rules! ngx_log_debug_mail {
+    ( $session:expr, $($arg:tt)+ ) => {
+        let log = unsafe { (*$session.connection()).log };
+        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Mail, log, $($arg)+);
+    }
+}
+
+/// Log to stream session connection log at level [`NGX_LOG_DEBUG_STREAM`].
+///
+/// [`NGX_LOG_DEBUG_STREAM`]: https://nginx.org/en/docs/dev/development_guide.html#logging
+#[macro_export]
+macro_rules! ngx_log_debug_stream {
+    ( $session:expr, $($arg:tt)+ ) => {
+        let log = unsafe { (*$session.connection()).log };
+        $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Stream, log, $($arg)+);
+    }
+}
+
 /// Log with requested debug mask.
 ///
 /// **NOTE:** This macro supports [`DebugMask::Http`] (`NGX_LOG_DEBUG_HTTP`), however, if you have
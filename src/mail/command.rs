@@ -0,0 +1,31 @@
+/// Define a static table of mail configuration directives.
+///
+/// Same as [`http_commands`](crate::http_commands), with the mail module contexts:
+///
+/// * `context` - the configuration blocks the directive is allowed in, either or both of `main` and `srv`,
+///   separated by `|`.
+/// * `conf` - the configuration the handler operates on: `main` or `srv`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// mail_commands! {
+///     static mut NGX_MAIL_FOO_COMMANDS = [
+///         "foo" => {
+///             context: main | srv,
+///             args: flag,
+///             conf: srv,
+///             set: |cf: &mut ngx_conf_t, conf: &mut SrvConf| {
+///                 conf.foo = core::parse_flag(cf, 1)?;
+///                 Ok(())
+///             },
+///         },
+///     ];
+/// }
+/// ```
+#[macro_export]
+macro_rules! mail_commands {
+    ( $( $body:tt )+ ) => {
+        $crate::ngx_commands! { mail; $( $body )+ }
+    };
+}
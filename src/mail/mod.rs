@@ -0,0 +1,20 @@
+mod command;
+mod module;
+mod session;
+
+pub use module::*;
+pub use session::*;
+
+use core::mem::offset_of;
+
+use crate::ffi::ngx_mail_conf_ctx_t;
+
+/// The offset of the `main_conf` field in the `ngx_mail_conf_ctx_t` struct.
+///
+/// This is used to access the main configuration context for a mail module.
+pub const NGX_MAIL_MAIN_CONF_OFFSET: usize = offset_of!(ngx_mail_conf_ctx_t, main_conf);
+
+/// The offset of the `srv_conf` field in the `ngx_mail_conf_ctx_t` struct.
+///
+/// This is used to access the server configuration context for a mail module.
+pub const NGX_MAIL_SRV_CONF_OFFSET: usize = offset_of!(ngx_mail_conf_ctx_t, srv_conf);
@@ -0,0 +1,71 @@
+use core::ffi::{c_char, c_void};
+use core::ptr;
+
+use crate::core::*;
+use crate::ffi::*;
+use crate::http::{merge_result, Merge};
+
+/// The `MailModule` trait provides the NGINX configuration stage interface for the `mail` modules.
+///
+/// These functions allocate structures, initialize them, and merge through the configuration
+/// layers. The configuration types implement the same [`Merge`] trait as the HTTP modules.
+///
+/// Unlike the HTTP and stream modules, the mail modules have no pre- and postconfiguration
+/// handlers. The `protocol` field of [`ngx_mail_module_t`] is only set by the protocol modules,
+/// e.g. `ngx_mail_pop3_module`, and should be null otherwise.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// static NGX_MAIL_FOO_MODULE_CTX: ngx_mail_module_t = ngx_mail_module_t {
+///     protocol: ptr::null_mut(),
+///     create_main_conf: Some(Module::create_main_conf),
+///     init_main_conf: Some(Module::init_main_conf),
+///     create_srv_conf: Some(Module::create_srv_conf),
+///     merge_srv_conf: Some(Module::merge_srv_conf),
+/// };
+/// ```
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#adding_new_modules> for details.
+pub trait MailModule {
+    /// Configuration in the `mail` block.
+    type MainConf: Merge + Default;
+    /// Configuration in a `server` block within the `mail` block.
+    type SrvConf: Merge + Default;
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn create_main_conf(cf: *mut ngx_conf_t) -> *mut c_void {
+        let mut pool = Pool::from_ngx_pool((*cf).pool);
+        pool.allocate::<Self::MainConf>(Default::default()) as *mut c_void
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn init_main_conf(_cf: *mut ngx_conf_t, _conf: *mut c_void) -> *mut c_char {
+        ptr::null_mut()
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn create_srv_conf(cf: *mut ngx_conf_t) -> *mut c_void {
+        let mut pool = Pool::from_ngx_pool((*cf).pool);
+        pool.allocate::<Self::SrvConf>(Default::default()) as *mut c_void
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn merge_srv_conf(cf: *mut ngx_conf_t, prev: *mut c_void, conf: *mut c_void) -> *mut c_char {
+        let prev = &mut *(prev as *mut Self::SrvConf);
+        let conf = &mut *(conf as *mut Self::SrvConf);
+        merge_result(cf, conf.merge(prev))
+    }
+}
@@ -0,0 +1,201 @@
+use core::ffi::c_void;
+
+use crate::core::*;
+use crate::ffi::*;
+
+/// Mail protocols.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    /// POP3, `NGX_MAIL_POP3_PROTOCOL`.
+    Pop3,
+    /// IMAP, `NGX_MAIL_IMAP_PROTOCOL`.
+    Imap,
+    /// SMTP, `NGX_MAIL_SMTP_PROTOCOL`.
+    Smtp,
+}
+
+impl TryFrom<ngx_uint_t> for Protocol {
+    type Error = ngx_uint_t;
+
+    fn try_from(value: ngx_uint_t) -> Result<Self, Self::Error> {
+        match value as u32 {
+            NGX_MAIL_POP3_PROTOCOL => Ok(Protocol::Pop3),
+            NGX_MAIL_IMAP_PROTOCOL => Ok(Protocol::Imap),
+            NGX_MAIL_SMTP_PROTOCOL => Ok(Protocol::Smtp),
+            _ => Err(value),
+        }
+    }
+}
+
+/// Wrapper struct for an [`ngx_mail_session_t`] pointer, providing methods for working with mail
+/// sessions.
+#[repr(transparent)]
+pub struct Session(ngx_mail_session_t);
+
+impl<'a> From<&'a Session> for *const ngx_mail_session_t {
+    fn from(session: &'a Session) -> Self {
+        &session.0 as *const _
+    }
+}
+
+impl<'a> From<&'a mut Session> for *mut ngx_mail_session_t {
+    fn from(session: &'a mut Session) -> Self {
+        &session.0 as *const _ as *mut _
+    }
+}
+
+impl Session {
+    /// Create a [`Session`] from an [`ngx_mail_session_t`].
+    ///
+    /// # Safety
+    ///
+    /// The caller has provided a valid non-null pointer to a valid `ngx_mail_session_t`
+    /// which shares the same representation as `Session`.
+    pub unsafe fn from_ngx_mail_session<'a>(s: *mut ngx_mail_session_t) -> &'a mut Session {
+        &mut *s.cast::<Session>()
+    }
+
+    /// Pointer to a [`ngx_connection_t`] client connection object.
+    ///
+    /// [`ngx_connection_t`]: https://nginx.org/en/docs/dev/development_guide.html#connection
+    pub fn connection(&self) -> *mut ngx_connection_t {
+        self.0.connection
+    }
+
+    /// Session pool, i.e. the pool of the client connection.
+    pub fn pool(&self) -> Pool {
+        // SAFETY: The session is allocated from the connection pool, thus it must be a valid pool.
+        unsafe { Pool::from_ngx_pool((*self.connection()).pool) }
+    }
+
+    /// Pointer to a [`ngx_log_t`].
+    ///
+    /// [`ngx_log_t`]: https://nginx.org/en/docs/dev/development_guide.html#logging
+    pub fn log(&self) -> *mut ngx_log_t {
+        unsafe { (*self.connection()).log }
+    }
+
+    /// Mail protocol of the session.
+    pub fn protocol(&self) -> Option<Protocol> {
+        Protocol::try_from(self.0.protocol() as ngx_uint_t).ok()
+    }
+
+    /// Text representation of the client address.
+    pub fn client_addr(&self) -> Option<&NgxStr> {
+        let addr = unsafe { self.0.addr_text.as_ref()? };
+        Some(unsafe { NgxStr::from_ngx_str(*addr) })
+    }
+
+    /// Authentication method, e.g. `NGX_MAIL_AUTH_PLAIN`.
+    pub fn auth_method(&self) -> ngx_uint_t {
+        self.0.auth_method() as ngx_uint_t
+    }
+
+    /// User name provided by the client for authentication.
+    pub fn login(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.login) }
+    }
+
+    /// Password provided by the client for authentication.
+    pub fn passwd(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.passwd) }
+    }
+
+    /// Salt used for the `APOP` and `CRAM-MD5` authentication methods.
+    pub fn salt(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.salt) }
+    }
+
+    /// Client host name, as resolved by NGINX for SMTP.
+    pub fn host(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.host) }
+    }
+
+    /// The `HELO` or `EHLO` argument of the SMTP client.
+    pub fn smtp_helo(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.smtp_helo) }
+    }
+
+    /// The `MAIL FROM` argument of the SMTP client.
+    pub fn smtp_from(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.smtp_from) }
+    }
+
+    /// The `RCPT TO` argument of the SMTP client.
+    pub fn smtp_to(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.smtp_to) }
+    }
+
+    /// Number of the failed authentication attempts.
+    pub fn login_attempt(&self) -> ngx_uint_t {
+        self.0.login_attempt
+    }
+
+    /// Global configuration for a module.
+    ///
+    /// Applies to the entire `mail` block.
+    ///
+    /// # Safety
+    /// Caller must ensure that type `T` matches the configuration type for the specified module.
+    pub fn get_module_main_conf<T>(&self, module: &ngx_module_t) -> Option<&'static T> {
+        // SAFETY: main conf is either NULL or allocated with ngx_p(c)alloc and
+        // explicitly initialized by the module
+        unsafe {
+            let mcf = *self.0.main_conf.add(module.ctx_index);
+            mcf.cast::<T>().as_ref()
+        }
+    }
+
+    /// Server-specific configuration for a module.
+    ///
+    /// Applies to a single `server` block.
+    ///
+    /// # Safety
+    /// Caller must ensure that type `T` matches the configuration type for the specified module.
+    pub fn get_module_srv_conf<T>(&self, module: &ngx_module_t) -> Option<&'static T> {
+        // SAFETY: server conf is either NULL or allocated with ngx_p(c)alloc and
+        // explicitly initialized by the module
+        unsafe {
+            let scf = *self.0.srv_conf.add(module.ctx_index);
+            scf.cast::<T>().as_ref()
+        }
+    }
+
+    /// Get Module context
+    pub fn get_module_ctx<T>(&self, module: &ngx_module_t) -> Option<&T> {
+        // SAFETY: ctx is either NULL or allocated with ngx_p(c)alloc and
+        // explicitly initialized by the module
+        unsafe {
+            let ctx = *self.0.ctx.add(module.ctx_index);
+            ctx.cast::<T>().as_ref()
+        }
+    }
+
+    /// Sets the value as the module's context.
+    pub fn set_module_ctx(&self, value: *mut c_void, module: &ngx_module_t) {
+        unsafe {
+            *self.0.ctx.add(module.ctx_index) = value;
+        };
+    }
+
+    /// Responds to the client with the protocol-specific internal error message and closes the
+    /// session.
+    ///
+    /// # Safety
+    ///
+    /// The session and its pool are freed, or scheduled to be freed once the response is sent. The
+    /// caller must not use the session, or any reference obtained from it, after this call.
+    pub unsafe fn internal_server_error(&mut self) {
+        unsafe { ngx_mail_session_internal_server_error(&mut self.0) }
+    }
+
+    /// Closes the client connection and releases the session.
+    ///
+    /// # Safety
+    ///
+    /// The session and its pool are freed. The caller must not use the session, or any reference
+    /// obtained from it, after this call.
+    pub unsafe fn close(&mut self) {
+        unsafe { ngx_mail_close_connection(self.connection()) }
+    }
+}
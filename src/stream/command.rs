@@ -0,0 +1,31 @@
+/// Define a static table of stream configuration directives.
+///
+/// Same as [`http_commands`](crate::http_commands), with the stream module contexts:
+///
+/// * `context` - the configuration blocks the directive is allowed in, any of `main`, `srv` and `ups`,
+///   separated by `|`.
+/// * `conf` - the configuration the handler operates on: `main` or `srv`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// stream_commands! {
+///     static mut NGX_STREAM_FOO_COMMANDS = [
+///         "foo" => {
+///             context: srv,
+///             args: flag,
+///             conf: srv,
+///             set: |cf: &mut ngx_conf_t, conf: &mut SrvConf| {
+///                 conf.foo = core::parse_flag(cf, 1)?;
+///                 Ok(())
+///             },
+///         },
+///     ];
+/// }
+/// ```
+#[macro_export]
+macro_rules! stream_commands {
+    ( $( $body:tt )+ ) => {
+        $crate::ngx_commands! { stream; $( $body )+ }
+    };
+}
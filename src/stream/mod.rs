@@ -0,0 +1,24 @@
+mod command;
+mod module;
+mod phase;
+mod session;
+mod variable;
+
+pub use module::*;
+pub use phase::*;
+pub use session::*;
+pub use variable::*;
+
+use core::mem::offset_of;
+
+use crate::ffi::ngx_stream_conf_ctx_t;
+
+/// The offset of the `main_conf` field in the `ngx_stream_conf_ctx_t` struct.
+///
+/// This is used to access the main configuration context for a stream module.
+pub const NGX_STREAM_MAIN_CONF_OFFSET: usize = offset_of!(ngx_stream_conf_ctx_t, main_conf);
+
+/// The offset of the `srv_conf` field in the `ngx_stream_conf_ctx_t` struct.
+///
+/// This is used to access the server configuration context for a stream module.
+pub const NGX_STREAM_SRV_CONF_OFFSET: usize = offset_of!(ngx_stream_conf_ctx_t, srv_conf);
@@ -0,0 +1,81 @@
+use core::ffi::{c_char, c_void};
+use core::ptr;
+
+use crate::core::*;
+use crate::ffi::*;
+use crate::http::{merge_result, Merge};
+use crate::stream::{add_variables, Variable};
+
+/// The `StreamModule` trait provides the NGINX configuration stage interface for the `stream`
+/// modules.
+///
+/// These functions allocate structures, initialize them, and merge through the configuration
+/// layers. The configuration types implement the same [`Merge`] trait as the HTTP modules.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#adding_new_modules> for details.
+pub trait StreamModule {
+    /// Configuration in the `stream` block.
+    type MainConf: Merge + Default;
+    /// Configuration in a `server` block within the `stream` block.
+    type SrvConf: Merge + Default;
+
+    /// Variables provided by the module.
+    ///
+    /// The variables are registered by the default [`StreamModule::preconfiguration`] handler.
+    const VARIABLES: &'static [Variable] = &[];
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn preconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+        match add_variables(&mut *cf, Self::VARIABLES) {
+            Ok(()) => Status::NGX_OK.into(),
+            Err(status) => status.into(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn postconfiguration(_cf: *mut ngx_conf_t) -> ngx_int_t {
+        Status::NGX_OK.into()
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn create_main_conf(cf: *mut ngx_conf_t) -> *mut c_void {
+        let mut pool = Pool::from_ngx_pool((*cf).pool);
+        pool.allocate::<Self::MainConf>(Default::default()) as *mut c_void
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn init_main_conf(_cf: *mut ngx_conf_t, _conf: *mut c_void) -> *mut c_char {
+        ptr::null_mut()
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn create_srv_conf(cf: *mut ngx_conf_t) -> *mut c_void {
+        let mut pool = Pool::from_ngx_pool((*cf).pool);
+        pool.allocate::<Self::SrvConf>(Default::default()) as *mut c_void
+    }
+
+    /// # Safety
+    ///
+    /// Callers should provide valid non-null `ngx_conf_t` arguments. Implementers must
+    /// guard against null inputs or risk runtime errors.
+    unsafe extern "C" fn merge_srv_conf(cf: *mut ngx_conf_t, prev: *mut c_void, conf: *mut c_void) -> *mut c_char {
+        let prev = &mut *(prev as *mut Self::SrvConf);
+        let conf = &mut *(conf as *mut Self::SrvConf);
+        merge_result(cf, conf.merge(prev))
+    }
+}
@@ -0,0 +1,114 @@
+use core::ptr::addr_of;
+
+use crate::core::Status;
+use crate::ffi::*;
+
+/// Stream session processing phases.
+///
+/// See <https://nginx.org/en/docs/stream/stream_processing.html>
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Phase {
+    /// The first phase, `NGX_STREAM_POST_ACCEPT_PHASE`. The realip module handlers run here.
+    PostAccept = ngx_stream_phases_NGX_STREAM_POST_ACCEPT_PHASE,
+    /// `NGX_STREAM_PREACCESS_PHASE`, the session limits, e.g. `limit_conn`.
+    PreAccess = ngx_stream_phases_NGX_STREAM_PREACCESS_PHASE,
+    /// `NGX_STREAM_ACCESS_PHASE`, the access checks, e.g. `allow` and `deny`.
+    Access = ngx_stream_phases_NGX_STREAM_ACCESS_PHASE,
+    /// `NGX_STREAM_SSL_PHASE`, the TLS termination.
+    Ssl = ngx_stream_phases_NGX_STREAM_SSL_PHASE,
+    /// `NGX_STREAM_PREREAD_PHASE`, the inspection of the initial client data, e.g. `ssl_preread`.
+    /// The handlers returning [`Status::NGX_AGAIN`] are invoked again once more data is read.
+    PreRead = ngx_stream_phases_NGX_STREAM_PREREAD_PHASE,
+    /// `NGX_STREAM_CONTENT_PHASE`, the session processing. The phase handlers are not invoked, see
+    /// [`set_content_handler`] instead.
+    Content = ngx_stream_phases_NGX_STREAM_CONTENT_PHASE,
+    /// `NGX_STREAM_LOG_PHASE`, the session logging.
+    Log = ngx_stream_phases_NGX_STREAM_LOG_PHASE,
+}
+
+impl From<Phase> for ngx_stream_phases {
+    fn from(value: Phase) -> Self {
+        value as ngx_stream_phases
+    }
+}
+
+/// Registers a session handler for the stream processing phase.
+///
+/// The handlers are stored in the stream core module configuration and can only be added from the
+/// [`StreamModule::postconfiguration`](crate::stream::StreamModule::postconfiguration) handler.
+/// Returns `Err(Status::NGX_ERROR)` for the [`Phase::Content`], if the phase handlers are not
+/// available or the memory allocation fails.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+///     match stream::register_phase_handler(&mut *cf, Phase::PreRead, preread_handler) {
+///         Ok(()) => Status::NGX_OK.into(),
+///         Err(status) => status.into(),
+///     }
+/// }
+/// ```
+pub fn register_phase_handler(
+    cf: &mut ngx_conf_t,
+    phase: Phase,
+    handler: unsafe extern "C" fn(*mut ngx_stream_session_t) -> ngx_int_t,
+) -> Result<(), Status> {
+    if phase == Phase::Content || cf.module_type != NGX_STREAM_MODULE as ngx_uint_t || cf.ctx.is_null() {
+        return Err(Status::NGX_ERROR);
+    }
+
+    // SAFETY: `cf` is the configuration of a stream module, so `ctx` points to
+    // `ngx_stream_conf_ctx_t` with the stream core module configuration.
+    let cmcf = unsafe {
+        let ctx = &*cf.ctx.cast::<ngx_stream_conf_ctx_t>();
+        let conf = *ctx.main_conf.add((*addr_of!(ngx_stream_core_module)).ctx_index);
+        conf.cast::<ngx_stream_core_main_conf_t>().as_mut()
+    };
+    let Some(cmcf) = cmcf else {
+        return Err(Status::NGX_ERROR);
+    };
+
+    let handlers = &mut cmcf.phases[phase as usize].handlers;
+    // The arrays are initialized right before the postconfiguration handlers are called.
+    if handlers.elts.is_null() {
+        return Err(Status::NGX_ERROR);
+    }
+
+    let h = unsafe { ngx_array_push(handlers) }.cast::<ngx_stream_handler_pt>();
+    if h.is_null() {
+        return Err(Status::NGX_ERROR);
+    }
+
+    unsafe { *h = Some(handler) };
+    Ok(())
+}
+
+/// Sets the content handler for the `server` block being configured.
+///
+/// The content handler is expected to be set by a directive handler, e.g. as `proxy_pass` or
+/// `return` do, and takes over the sessions in the `server` block once the phases are passed.
+/// Returns `Err(Status::NGX_ERROR)` if the stream server configuration is not available.
+pub fn set_content_handler(
+    cf: &mut ngx_conf_t,
+    handler: unsafe extern "C" fn(*mut ngx_stream_session_t),
+) -> Result<(), Status> {
+    if cf.module_type != NGX_STREAM_MODULE as ngx_uint_t || cf.ctx.is_null() {
+        return Err(Status::NGX_ERROR);
+    }
+
+    // SAFETY: `cf` is the configuration of a stream module, so `ctx` points to
+    // `ngx_stream_conf_ctx_t` with the stream core module configuration.
+    let cscf = unsafe {
+        let ctx = &*cf.ctx.cast::<ngx_stream_conf_ctx_t>();
+        let conf = *ctx.srv_conf.add((*addr_of!(ngx_stream_core_module)).ctx_index);
+        conf.cast::<ngx_stream_core_srv_conf_t>().as_mut()
+    };
+    let Some(cscf) = cscf else {
+        return Err(Status::NGX_ERROR);
+    };
+
+    cscf.handler = Some(handler);
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use core::ffi::c_void;
+
+use crate::core::*;
+use crate::ffi::*;
+
+/// Define a static stream session handler.
+///
+/// Handlers are expected to take a single [`Session`] argument and return a [`Status`]. The
+/// handlers are registered for the stream processing phases with
+/// [`register_phase_handler`](crate::stream::register_phase_handler).
+#[macro_export]
+macro_rules! stream_session_handler {
+    ( $name: ident, $handler: expr ) => {
+        extern "C" fn $name(s: *mut $crate::ffi::ngx_stream_session_t) -> $crate::ffi::ngx_int_t {
+            let status: $crate::core::Status =
+                $handler(unsafe { &mut $crate::stream::Session::from_ngx_stream_session(s) });
+            status.0
+        }
+    };
+}
+
+/// Define a static stream content handler.
+///
+/// The content handler takes over the session once all the phases are passed and is expected to
+/// take a single [`Session`] argument. The session must be finalized with
+/// [`Session::finalize`] when the processing is complete.
+#[macro_export]
+macro_rules! stream_content_handler {
+    ( $name: ident, $handler: expr ) => {
+        extern "C" fn $name(s: *mut $crate::ffi::ngx_stream_session_t) {
+            $handler(unsafe { &mut $crate::stream::Session::from_ngx_stream_session(s) });
+        }
+    };
+}
+
+/// Wrapper struct for an [`ngx_stream_session_t`] pointer, providing methods for working with
+/// stream sessions.
+#[repr(transparent)]
+pub struct Session(ngx_stream_session_t);
+
+impl<'a> From<&'a Session> for *const ngx_stream_session_t {
+    fn from(session: &'a Session) -> Self {
+        &session.0 as *const _
+    }
+}
+
+impl<'a> From<&'a mut Session> for *mut ngx_stream_session_t {
+    fn from(session: &'a mut Session) -> Self {
+        &session.0 as *const _ as *mut _
+    }
+}
+
+impl Session {
+    /// Create a [`Session`] from an [`ngx_stream_session_t`].
+    ///
+    /// # Safety
+    ///
+    /// The caller has provided a valid non-null pointer to a valid `ngx_stream_session_t`
+    /// which shares the same representation as `Session`.
+    pub unsafe fn from_ngx_stream_session<'a>(s: *mut ngx_stream_session_t) -> &'a mut Session {
+        &mut *s.cast::<Session>()
+    }
+
+    /// Pointer to a [`ngx_connection_t`] client connection object.
+    ///
+    /// [`ngx_connection_t`]: https://nginx.org/en/docs/dev/development_guide.html#connection
+    pub fn connection(&self) -> *mut ngx_connection_t {
+        self.0.connection
+    }
+
+    /// Session pool, i.e. the pool of the client connection.
+    pub fn pool(&self) -> Pool {
+        // SAFETY: The session is allocated from the connection pool, thus it must be a valid pool.
+        unsafe { Pool::from_ngx_pool((*self.connection()).pool) }
+    }
+
+    /// Pointer to a [`ngx_log_t`].
+    ///
+    /// [`ngx_log_t`]: https://nginx.org/en/docs/dev/development_guide.html#logging
+    pub fn log(&self) -> *mut ngx_log_t {
+        unsafe { (*self.connection()).log }
+    }
+
+    /// Number of bytes received from the client.
+    pub fn received(&self) -> off_t {
+        self.0.received
+    }
+
+    /// Session status code, e.g. [`NGX_STREAM_OK`], as reported by the `$status` variable.
+    pub fn status(&self) -> ngx_uint_t {
+        self.0.status
+    }
+
+    /// Global configuration for a module.
+    ///
+    /// Applies to the entire `stream` block.
+    ///
+    /// # Safety
+    /// Caller must ensure that type `T` matches the configuration type for the specified module.
+    pub fn get_module_main_conf<T>(&self, module: &ngx_module_t) -> Option<&'static T> {
+        // SAFETY: main conf is either NULL or allocated with ngx_p(c)alloc and
+        // explicitly initialized by the module
+        unsafe {
+            let mcf = *self.0.main_conf.add(module.ctx_index);
+            mcf.cast::<T>().as_ref()
+        }
+    }
+
+    /// Server-specific configuration for a module.
+    ///
+    /// Applies to a single `server` block.
+    ///
+    /// # Safety
+    /// Caller must ensure that type `T` matches the configuration type for the specified module.
+    pub fn get_module_srv_conf<T>(&self, module: &ngx_module_t) -> Option<&'static T> {
+        // SAFETY: server conf is either NULL or allocated with ngx_p(c)alloc and
+        // explicitly initialized by the module
+        unsafe {
+            let scf = *self.0.srv_conf.add(module.ctx_index);
+            scf.cast::<T>().as_ref()
+        }
+    }
+
+    /// Get Module context
+    pub fn get_module_ctx<T>(&self, module: &ngx_module_t) -> Option<&T> {
+        // SAFETY: ctx is either NULL or allocated with ngx_p(c)alloc and
+        // explicitly initialized by the module
+        unsafe {
+            let ctx = *self.0.ctx.add(module.ctx_index);
+            ctx.cast::<T>().as_ref()
+        }
+    }
+
+    /// Sets the value as the module's context.
+    pub fn set_module_ctx(&self, value: *mut c_void, module: &ngx_module_t) {
+        unsafe {
+            *self.0.ctx.add(module.ctx_index) = value;
+        };
+    }
+
+    /// Finalizes the session with the status code, e.g. [`NGX_STREAM_OK`] or
+    /// [`NGX_STREAM_INTERNAL_SERVER_ERROR`], and closes the client connection.
+    ///
+    /// # Safety
+    ///
+    /// The session and its pool are freed. The caller must not use the session, or any reference
+    /// obtained from it, after this call.
+    pub unsafe fn finalize(&mut self, status: ngx_uint_t) {
+        unsafe { ngx_stream_finalize_session(&mut self.0, status) }
+    }
+}
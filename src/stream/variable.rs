@@ -0,0 +1,296 @@
+use core::ptr::{self, addr_of};
+use core::slice;
+
+use crate::core::{
+    alloc_str, find_prefix_variable, variable_get_handler, variable_key, variable_prefix_get_handler,
+    variable_set_handler, variable_value, ModuleVariable, NgxStr, Status, VariableContext,
+};
+use crate::ffi::*;
+use crate::stream::Session;
+
+pub use crate::core::VariableFlags;
+
+/// Stream variable getter.
+///
+/// Returns the value of the variable, or `None` if the variable is not found, or an error if the
+/// evaluation failed. The returned slice
+/// must remain valid for the lifetime of the session, e.g. point to the session data or be
+/// allocated from the session pool with [`Session::alloc_str`].
+pub type VariableGetter = for<'s> fn(&'s mut Session) -> Result<Option<&'s [u8]>, Status>;
+
+/// Stream prefix variable getter, invoked with the rest of the requested variable name.
+///
+/// See [`Variable::prefix_getter`](crate::core::ModuleVariable::prefix_getter).
+pub type VariablePrefixGetter = for<'s, 'n> fn(&'s mut Session, &'n NgxStr) -> Result<Option<&'s [u8]>, Status>;
+
+/// Stream variable setter, invoked for the changeable variables by the `set` directive.
+pub type VariableSetter = for<'s> fn(&'s mut Session, &'s NgxStr);
+
+/// Definition of a stream variable provided by a module.
+///
+/// The variables are registered with [`add_variables`], usually from the
+/// [`StreamModule::preconfiguration`](crate::stream::StreamModule::preconfiguration) handler,
+/// where the default implementation registers the
+/// [`StreamModule::VARIABLES`](crate::stream::StreamModule::VARIABLES).
+///
+/// # Example
+///
+/// ```rust,ignore
+/// impl StreamModule for Module {
+///     // ...
+///     const VARIABLES: &'static [Variable] = &[
+///         Variable::new("proto_detected").getter(|session| {
+///             let ctx = session.get_module_ctx::<SessionCtx>(unsafe { &*addr_of!(ngx_stream_proto_module) });
+///             Ok(ctx.map(|ctx| ctx.protocol.as_bytes()))
+///         }),
+///         // $proto_header_user_agent, $proto_header_host, ...
+///         Variable::new("proto_header_").prefix_getter(|session, name| {
+///             let ctx = session.get_module_ctx::<SessionCtx>(unsafe { &*addr_of!(ngx_stream_proto_module) });
+///             Ok(ctx.and_then(|ctx| ctx.header(name.as_bytes())))
+///         }),
+///     ];
+/// }
+/// ```
+pub type Variable = ModuleVariable<Session>;
+
+impl VariableContext for Session {
+    type Raw = ngx_stream_session_t;
+
+    unsafe fn from_raw<'a>(raw: *mut ngx_stream_session_t) -> &'a mut Self {
+        Session::from_ngx_stream_session(raw)
+    }
+
+    fn prefix_variable(&self, name: &[u8]) -> Option<&'static Variable> {
+        let cmcf =
+            self.get_module_main_conf::<ngx_stream_core_main_conf_t>(unsafe { &*addr_of!(ngx_stream_core_module) })?;
+        let variables: &[ngx_stream_variable_t] = match cmcf.prefix_variables.nelts {
+            0 => &[],
+            // SAFETY: the array contains `ngx_stream_variable_t` elements.
+            n => unsafe { slice::from_raw_parts(cmcf.prefix_variables.elts.cast(), n) },
+        };
+
+        find_prefix(variables, prefix_get_handler, name)
+    }
+}
+
+/// The `get_handler` of a stream variable.
+type GetHandler = unsafe extern "C" fn(*mut ngx_stream_session_t, *mut ngx_stream_variable_value_t, usize) -> ngx_int_t;
+
+/// Finds the prefix variable registered with `handler`, see [`VariableContext::prefix_variable`].
+fn find_prefix(variables: &[ngx_stream_variable_t], handler: GetHandler, name: &[u8]) -> Option<&'static Variable> {
+    find_prefix_variable(
+        variables
+            .iter()
+            .map(|v| (&v.name, v.get_handler.map_or(ptr::null(), |f| f as *const ()), v.data)),
+        handler as *const (),
+        name,
+    )
+}
+
+/// The `get_handler` of the stream prefix variables.
+///
+/// The handler is not generic, so that its address identifies the variables registered by the
+/// crate in [`Session::prefix_variable`](VariableContext::prefix_variable).
+unsafe extern "C" fn prefix_get_handler(
+    s: *mut ngx_stream_session_t,
+    v: *mut ngx_stream_variable_value_t,
+    data: usize,
+) -> ngx_int_t {
+    variable_prefix_get_handler::<Session>(s, v, data)
+}
+
+/// Index of a stream variable, obtained at the configuration time.
+///
+/// See [`Session::indexed_variable`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VariableIndex(ngx_uint_t);
+
+impl VariableIndex {
+    /// Gets the index of the variable with the given name, without the leading `$`.
+    ///
+    /// The variable does not have to be defined yet. The existence of the variable is checked by
+    /// NGINX at the end of the configuration parsing.
+    pub fn new(cf: &mut ngx_conf_t, name: &str) -> Result<Self, Status> {
+        let mut name = ngx_str_t {
+            len: name.len(),
+            data: name.as_ptr().cast_mut(),
+        };
+
+        let index = unsafe { ngx_stream_get_variable_index(cf, &mut name) };
+        if index == NGX_ERROR as ngx_int_t {
+            return Err(Status::NGX_ERROR);
+        }
+
+        Ok(VariableIndex(index as ngx_uint_t))
+    }
+
+    /// Returns the raw index value.
+    pub fn index(&self) -> ngx_uint_t {
+        self.0
+    }
+}
+
+/// Registers the variables with `ngx_stream_add_variable`.
+///
+/// Returns `Err(Status::NGX_ERROR)` if the variable cannot be added, e.g. because of a conflict
+/// with an existing variable. The error is logged by NGINX.
+pub fn add_variables(cf: &mut ngx_conf_t, variables: &'static [Variable]) -> Result<(), Status> {
+    for var in variables {
+        let mut name = var.ngx_name();
+
+        let v = unsafe { ngx_stream_add_variable(cf, &mut name, var.ngx_flags()) };
+        let Some(v) = (unsafe { v.as_mut() }) else {
+            return Err(Status::NGX_ERROR);
+        };
+
+        if var.is_prefix() {
+            v.get_handler = Some(prefix_get_handler);
+        } else {
+            if var.get.is_some() {
+                v.get_handler = Some(variable_get_handler::<Session>);
+            }
+            if var.set.is_some() {
+                v.set_handler = Some(variable_set_handler::<Session>);
+            }
+        }
+        v.data = var as *const Variable as usize;
+    }
+
+    Ok(())
+}
+
+impl Session {
+    /// Value of the variable `name`, without the leading `$`.
+    ///
+    /// Returns `None` if the variable is unknown, has no value or the evaluation failed. Prefer
+    /// [`Session::indexed_variable`] for the variables known at the configuration time.
+    pub fn variable(&self, name: &str) -> Option<&NgxStr> {
+        let s: *const ngx_stream_session_t = self.into();
+
+        let (mut name, key) = variable_key(self.pool(), name)?;
+
+        let v = unsafe { ngx_stream_get_variable(s.cast_mut(), &mut name, key) };
+        variable_value(v)
+    }
+
+    /// Value of the variable with the given index.
+    ///
+    /// Returns `None` if the variable has no value or the evaluation failed.
+    pub fn indexed_variable(&self, index: VariableIndex) -> Option<&NgxStr> {
+        let s: *const ngx_stream_session_t = self.into();
+        let v = unsafe { ngx_stream_get_indexed_variable(s.cast_mut(), index.0) };
+        variable_value(v)
+    }
+
+    /// Copies the string to the session pool.
+    ///
+    /// The returned slice remains valid for the lifetime of the session, which makes it suitable
+    /// for the [`VariableGetter`] values. Returns `Err(Status::NGX_ERROR)` if the memory cannot be
+    /// allocated.
+    pub fn alloc_str(&self, value: impl AsRef<[u8]>) -> Result<&[u8], Status> {
+        alloc_str(self.pool(), value.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SNI: Variable = Variable::new("sni_").prefix_getter(|_, _| Ok(None));
+    static SNI_HOST: Variable = Variable::new("sni_host_").prefix_getter(|_, _| Ok(None));
+
+    // The tests cannot reference `prefix_get_handler`, as it pulls in the NGINX module symbols.
+    unsafe extern "C" fn crate_handler(
+        _s: *mut ngx_stream_session_t,
+        _v: *mut ngx_stream_variable_value_t,
+        _data: usize,
+    ) -> ngx_int_t {
+        NGX_OK as _
+    }
+
+    unsafe extern "C" fn other_handler(
+        _s: *mut ngx_stream_session_t,
+        _v: *mut ngx_stream_variable_value_t,
+        _data: usize,
+    ) -> ngx_int_t {
+        NGX_OK as _
+    }
+
+    fn prefix_variable(name: &'static str, handler: ngx_stream_get_variable_pt, data: usize) -> ngx_stream_variable_t {
+        ngx_stream_variable_t {
+            name: ngx_str_t {
+                len: name.len(),
+                data: name.as_ptr().cast_mut(),
+            },
+            set_handler: None,
+            get_handler: handler,
+            data,
+            flags: NGX_STREAM_VAR_PREFIX as _,
+            index: 0,
+        }
+    }
+
+    fn has_flags(bits: ngx_uint_t, flags: VariableFlags) -> bool {
+        bits & flags.bits() == flags.bits()
+    }
+
+    fn registered(var: &'static Variable) -> ngx_stream_variable_t {
+        prefix_variable(var.name(), Some(crate_handler), var as *const Variable as usize)
+    }
+
+    #[test]
+    fn prefix_flags() {
+        assert!(SNI.is_prefix());
+        assert!(has_flags(SNI.ngx_flags(), VariableFlags::PREFIX));
+
+        let var = Variable::new("sni_")
+            .prefix_getter(|_, _| Ok(None))
+            .flags(VariableFlags::NOCACHEABLE);
+        assert!(var.is_prefix());
+        assert!(has_flags(
+            var.ngx_flags(),
+            VariableFlags::PREFIX | VariableFlags::NOCACHEABLE
+        ));
+
+        let var = Variable::new("sni").getter(|_| Ok(None));
+        assert!(!var.is_prefix());
+        assert!(!has_flags(var.ngx_flags(), VariableFlags::PREFIX));
+    }
+
+    #[test]
+    fn prefix_lookup() {
+        let variables = [registered(&SNI), registered(&SNI_HOST)];
+
+        let found = find_prefix(&variables, crate_handler, b"sni_example.com").unwrap();
+        assert!(core::ptr::eq(found, &SNI));
+        // NGINX invokes the first matching prefix variable.
+        let found = find_prefix(&variables, crate_handler, b"sni_host_example.com").unwrap();
+        assert!(core::ptr::eq(found, &SNI));
+        let found = find_prefix(&variables, crate_handler, b"sni_").unwrap();
+        assert!(core::ptr::eq(found, &SNI));
+
+        let variables = [registered(&SNI_HOST), registered(&SNI)];
+        let found = find_prefix(&variables, crate_handler, b"sni_host_example.com").unwrap();
+        assert!(core::ptr::eq(found, &SNI_HOST));
+
+        assert!(find_prefix(&variables, crate_handler, b"sni").is_none());
+        assert!(find_prefix(&variables, crate_handler, b"ssl_server_name").is_none());
+        assert!(find_prefix(&[], crate_handler, b"sni_example.com").is_none());
+    }
+
+    #[test]
+    fn prefix_lookup_foreign() {
+        let variables = [
+            prefix_variable("sn", Some(other_handler), 0),
+            registered(&SNI),
+            prefix_variable("upstream_", Some(other_handler), 1),
+        ];
+
+        // The variable is served by another module.
+        assert!(find_prefix(&variables, crate_handler, b"sni_example.com").is_none());
+        assert!(find_prefix(&variables, crate_handler, b"upstream_addr").is_none());
+
+        let variables = [prefix_variable("sni_", None, 0), registered(&SNI)];
+        assert!(find_prefix(&variables, crate_handler, b"sni_example.com").is_none());
+    }
+}
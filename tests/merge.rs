@@ -0,0 +1,78 @@
+#![cfg(feature = "derive")]
+
+use ngx::http::{Merge, MergeConfigError, Unset};
+
+#[derive(Debug, Merge)]
+struct Timeouts {
+    #[merge(default = 60000)]
+    read: usize,
+}
+
+#[derive(Debug, Merge)]
+struct LocConf {
+    #[merge(default = false)]
+    enable: Option<bool>,
+    #[merge(required)]
+    name: Option<String>,
+    level: isize,
+    #[merge(nested)]
+    timeouts: Timeouts,
+    #[merge(skip)]
+    hits: u32,
+}
+
+impl Default for LocConf {
+    fn default() -> Self {
+        LocConf {
+            enable: Unset::UNSET,
+            name: Unset::UNSET,
+            level: Unset::UNSET,
+            timeouts: Timeouts { read: Unset::UNSET },
+            hits: 0,
+        }
+    }
+}
+
+#[test]
+fn test_merge_inherits_unset_fields() {
+    let prev = LocConf {
+        name: Some("upstream".to_string()),
+        level: 2,
+        hits: 10,
+        ..Default::default()
+    };
+
+    let mut conf = LocConf {
+        level: 1,
+        ..Default::default()
+    };
+    conf.merge(&prev).unwrap();
+
+    assert_eq!(conf.enable, Some(false));
+    assert_eq!(conf.name.as_deref(), Some("upstream"));
+    assert_eq!(conf.level, 1);
+    assert_eq!(conf.timeouts.read, 60000);
+    assert_eq!(conf.hits, 0);
+}
+
+#[test]
+fn test_merge_required_field() {
+    let mut conf = LocConf::default();
+    let err = conf.merge(&LocConf::default()).unwrap_err();
+
+    assert!(matches!(err, MergeConfigError::MissingField("name")));
+    assert_eq!(err.to_string(), "no value for \"name\"");
+}
+
+#[test]
+fn test_merge_error_messages() {
+    let err = MergeConfigError::invalid_value("level", -1, "must be positive");
+    assert!(matches!(
+        err,
+        MergeConfigError::InvalidValue { field: "level", ref value, .. } if value == "-1"
+    ));
+    assert_eq!(err.to_string(), "invalid value \"-1\" for \"level\": must be positive");
+
+    let err = MergeConfigError::Message("\"name\" and \"level\" are mutually exclusive".into());
+    assert_eq!(err.to_string(), "\"name\" and \"level\" are mutually exclusive");
+}